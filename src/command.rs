@@ -0,0 +1,561 @@
+use std::cmp::min;
+use direction::Direction;
+
+// Everything the player can ask the game to do. `parse` turns a line
+// of input into one of these, and Game::perform carries it out.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Command {
+    Move(Direction),
+    // `count` is how many to move, if the player said; otherwise
+    // it's the whole stack
+    PickUp { item: String, count: Option<u64> },
+    Take {
+        item: String,
+        count: Option<u64>,
+        from: Option<String>,
+    },
+    Drop { item: String, count: Option<u64> },
+    Give {
+        item: String,
+        count: Option<u64>,
+        to: String,
+    },
+    Put {
+        item: String,
+        count: Option<u64>,
+        into: String,
+    },
+    // swap `offer` for one of an NPC's items
+    Trade {
+        offer: String,
+        count: Option<u64>,
+        item: String,
+        with: Option<String>,
+    },
+    // trade the world's currency for one of an NPC's items
+    Buy { item: String, from: Option<String> },
+    Use(String),
+    Unlock { what: Lockable, with: String },
+    Open(String),
+    Close(String),
+    Look,
+    LookIn(String),
+    Talk(Option<String>), // who to talk to, if the player said
+    // fight an NPC, with a weapon or without
+    Attack {
+        npc: Option<String>,
+        with: Option<String>,
+    },
+    Map,
+    Inventory,
+    Status,
+    Quests,
+    Help,
+    Quit,
+    Save(String),
+    Load(String),
+    Restart,
+    Undo,
+    Redo,
+}
+
+// Something with a lock on it: one of the current room's exits, or a
+// container item like a chest.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Lockable {
+    Exit(Direction),
+    Item(String),
+}
+
+impl Command {
+    // Whether doing this lets the world move on a turn (see
+    // Game::tick), even if it doesn't work, like taking something
+    // that isn't there. Looking at things and saving don't.
+    pub fn takes_time(&self) -> bool {
+        !matches!(*self,
+                  Command::Look | Command::Map | Command::Inventory | Command::Status |
+                  Command::Quests | Command::Help | Command::Quit | Command::Save(_) |
+                  Command::Load(_) | Command::Restart | Command::Undo | Command::Redo)
+    }
+
+    // Whether the player can still do this once it's game over.
+    pub fn works_after_game_over(&self) -> bool {
+        matches!(*self,
+                 Command::Help | Command::Quit | Command::Load(_) | Command::Restart |
+                 Command::Undo | Command::Redo)
+    }
+}
+
+// Why a line of input isn't a Command.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ParseError {
+    Empty,
+    // the first word isn't a command we know; `suggestion` is a
+    // command that is spelled almost the same, if there is one
+    UnknownVerb { verb: String, suggestion: Option<String> },
+    // a command like "use" that needs to be told what to use
+    MissingObject { verb: String },
+    UnknownDirection { dir: String },
+    // a command we know, followed by words it doesn't understand,
+    // like "look at chest"
+    UnexpectedWords { verb: String, words: String },
+}
+
+// The first word of every command, used to suggest what the player
+// might have meant when they make a typo. Short aliases like "n" and
+// "i" are left out, since almost anything is a typo away from them.
+const VERBS: &[&str] = &["north", "south", "east", "west", "go", "move", "walk", "pick", "get",
+                         "grab", "take", "drop", "give", "put", "trade", "buy", "use", "unlock",
+                         "open", "close", "shut", "look", "talk", "attack", "fight", "hit", "kill",
+                         "map", "display", "show", "inventory", "print", "status", "stats",
+                         "quests", "help", "exit", "quit", "save", "load", "restart", "undo",
+                         "redo"];
+
+// Split a line of input into lowercase words, so that "Pick  UP the
+// Potion" and "pick up the potion" mean the same thing.
+pub fn tokenize(input: &str) -> Vec<String> {
+    input.split_whitespace().map(|word| word.to_lowercase()).collect()
+}
+
+pub fn parse(input: &str) -> Result<Command, ParseError> {
+    let words = tokenize(input);
+
+    let (verb, rest) = match words.split_first() {
+        Some((verb, rest)) => (verb.as_str(), rest),
+        None => return Err(ParseError::Empty),
+    };
+
+    // "north" and "n" on their own mean "go north"
+    if rest.is_empty() {
+        if let Ok(dir) = verb.parse() {
+            return Ok(Command::Move(dir));
+        }
+    }
+
+    match verb {
+        "go" | "move" | "walk" => {
+            let dir = object(verb, rest)?;
+            dir.parse()
+                .map(Command::Move)
+                .map_err(|_| ParseError::UnknownDirection { dir: dir })
+        }
+        "pick" | "get" | "grab" => {
+            let (count, item) = counted(verb, skip("up", rest))?;
+            Ok(Command::PickUp {
+                item: item,
+                count: count,
+            })
+        }
+        "take" => {
+            // "take hat", "take 2 hats" or "take hat from Eve"
+            let (items, from) = match split(rest, &["from"]) {
+                Some((items, from)) => (items, Some(object(verb, from)?)),
+                None => (rest, None),
+            };
+            let (count, item) = counted(verb, items)?;
+
+            Ok(Command::Take {
+                item: item,
+                count: count,
+                from: from,
+            })
+        }
+        "drop" => {
+            let (count, item) = counted(verb, rest)?;
+            Ok(Command::Drop {
+                item: item,
+                count: count,
+            })
+        }
+        "give" => {
+            // "give 2 potions to Eve"
+            let (items, to) = split_at(verb, rest, &["to"])?;
+            let (count, item) = counted(verb, items)?;
+
+            Ok(Command::Give {
+                item: item,
+                count: count,
+                to: object(verb, to)?,
+            })
+        }
+        "put" => {
+            // "put potion in bag"
+            let (items, into) = split_at(verb, rest, &["in", "into", "inside"])?;
+            let (count, item) = counted(verb, items)?;
+
+            Ok(Command::Put {
+                item: item,
+                count: count,
+                into: object(verb, into)?,
+            })
+        }
+        "trade" => {
+            // "trade 2 potions for hat" or "trade potion for hat with Eve"
+            let (offer, rest) = split_at(verb, rest, &["for"])?;
+            let (count, offer) = counted(verb, offer)?;
+            let (item, with) = match split(rest, &["with"]) {
+                Some((item, with)) => (item, Some(object(verb, with)?)),
+                None => (rest, None),
+            };
+
+            Ok(Command::Trade {
+                offer: offer,
+                count: count,
+                item: object(verb, item)?,
+                with: with,
+            })
+        }
+        "buy" => {
+            // "buy hat" or "buy hat from Eve"
+            let (item, from) = match split(rest, &["from"]) {
+                Some((item, from)) => (item, Some(object(verb, from)?)),
+                None => (rest, None),
+            };
+
+            Ok(Command::Buy {
+                item: object(verb, item)?,
+                from: from,
+            })
+        }
+        "use" => object(verb, rest).map(Command::Use),
+        "unlock" => {
+            // "unlock north with brass key" or "unlock chest with brass key"
+            let (what, with) = split_at(verb, rest, &["with"])?;
+            let what = match what.first().map(|word| word.parse()) {
+                Some(Ok(dir)) if what.len() == 1 => Lockable::Exit(dir),
+                _ => Lockable::Item(object(verb, what)?),
+            };
+
+            Ok(Command::Unlock {
+                what: what,
+                with: object(verb, with)?,
+            })
+        }
+        "open" => object(verb, rest).map(Command::Open),
+        "close" | "shut" => object(verb, rest).map(Command::Close),
+        "look" | "l" if rest.is_empty() || rest == ["around"] => Ok(Command::Look),
+        "look" | "l" if rest[0] == "in" || rest[0] == "inside" => {
+            object("look in", &rest[1..]).map(Command::LookIn)
+        }
+        "talk" => {
+            let rest = skip("to", rest);
+            if rest.is_empty() {
+                Ok(Command::Talk(None))
+            } else {
+                object(verb, rest).map(|npc| Command::Talk(Some(npc)))
+            }
+        }
+        "attack" | "fight" | "hit" | "kill" => {
+            // "attack", "attack goblin" or "attack the goblin with a sword"
+            let (npc, with) = match split(rest, &["with"]) {
+                Some((npc, with)) => (npc, Some(object(verb, skip_article(with))?)),
+                None => (rest, None),
+            };
+            let npc = skip_article(npc);
+
+            Ok(Command::Attack {
+                npc: if npc.is_empty() { None } else { Some(object(verb, npc)?) },
+                with: with,
+            })
+        }
+        "map" | "m" => Ok(Command::Map),
+        "display" | "show" if rest == ["map"] => Ok(Command::Map),
+        "display" | "show" | "print" if rest == ["inventory"] => Ok(Command::Inventory),
+        "inventory" | "inv" | "i" => Ok(Command::Inventory),
+        "status" | "stats" => Ok(Command::Status),
+        "quests" | "quest" => Ok(Command::Quests),
+        "help" | "h" | "?" => Ok(Command::Help),
+        "exit" | "quit" | "q" => Ok(Command::Quit),
+        "save" => object(verb, rest).map(Command::Save),
+        "load" => object(verb, rest).map(Command::Load),
+        "restart" => Ok(Command::Restart),
+        "undo" => Ok(Command::Undo),
+        "redo" => Ok(Command::Redo),
+        "look" | "l" | "display" | "show" | "print" => {
+            Err(ParseError::UnexpectedWords {
+                verb: verb.to_string(),
+                words: rest.join(" "),
+            })
+        }
+        _ => {
+            Err(ParseError::UnknownVerb {
+                verb: verb.to_string(),
+                suggestion: suggest(verb),
+            })
+        }
+    }
+}
+
+// Drop `word` from the front of `words` if it is there, so that
+// "pick up X" and "pick X" both work.
+fn skip<'a>(word: &str, words: &'a [String]) -> &'a [String] {
+    match words.split_first() {
+        Some((first, rest)) if first == word => rest,
+        _ => words,
+    }
+}
+
+// Drop "the", "a" or "an" from the front of `words`, since NPCs and
+// items are called by their names alone.
+fn skip_article(words: &[String]) -> &[String] {
+    match words.split_first() {
+        Some((first, rest)) if ["the", "a", "an"].contains(&first.as_str()) => rest,
+        _ => words,
+    }
+}
+
+// The thing a command acts on, like "cool potion" in "use cool potion".
+fn object(verb: &str, words: &[String]) -> Result<String, ParseError> {
+    if words.is_empty() {
+        Err(ParseError::MissingObject { verb: verb.to_string() })
+    } else {
+        Ok(words.join(" "))
+    }
+}
+
+// Split `words` at the first of `separators`, if it's there.
+fn split<'a>(words: &'a [String], separators: &[&str]) -> Option<(&'a [String], &'a [String])> {
+    words.iter()
+        .position(|word| separators.contains(&word.as_str()))
+        .map(|i| (&words[..i], &words[i + 1..]))
+}
+
+// Split `words` at the first of `separators`, for commands like "give
+// X to Y" that need two objects. The separator has to be there.
+fn split_at<'a>(verb: &str,
+                words: &'a [String],
+                separators: &[&str])
+                -> Result<(&'a [String], &'a [String]), ParseError> {
+    match split(words, separators) {
+        Some(halves) => Ok(halves),
+        None if words.is_empty() => Err(ParseError::MissingObject { verb: verb.to_string() }),
+        None => {
+            let verb = format!("{} {} {}", verb, words.join(" "), separators[0]);
+            Err(ParseError::MissingObject { verb: verb })
+        }
+    }
+}
+
+// An object that may start with how many of it the player means, like
+// "2 potions". The count has to be more than zero; anything else is
+// taken to be part of the name.
+fn counted(verb: &str, words: &[String]) -> Result<(Option<u64>, String), ParseError> {
+    match words.split_first() {
+        Some((first, rest)) if !rest.is_empty() => {
+            match first.parse() {
+                Ok(count) if count > 0 => Ok((Some(count), object(verb, rest)?)),
+                _ => Ok((None, object(verb, words)?)),
+            }
+        }
+        _ => Ok((None, object(verb, words)?)),
+    }
+}
+
+// The known verb that is closest to `verb`, if any is close enough
+// to plausibly be a typo.
+fn suggest(verb: &str) -> Option<String> {
+    VERBS.iter()
+        .filter(|&&known| known != verb)
+        .map(|known| (edit_distance(verb, known), known))
+        .filter(|&(distance, known)| distance <= 2 && distance < known.len())
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, known)| known.to_string())
+}
+
+// The number of single character insertions, deletions,
+// substitutions and swaps of two neighbouring characters it takes
+// to turn `a` into `b`. Counting swaps as one edit makes "tlak" closer
+// to "talk" than to "take".
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // d[i][j] is the distance between the first i characters of `a`
+    // and the first j characters of `b`.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = min(d[i - 1][j - 1] + cost, min(d[i - 1][j], d[i][j - 1]) + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = min(d[i][j], d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_movement_in_every_spelling() {
+        for input in &["move north", "go north", "walk N", "north", "n", "  GO   North "] {
+            assert_eq!(parse(input), Ok(Command::Move(Direction::North)));
+        }
+    }
+
+    #[test]
+    fn parses_item_commands_and_aliases() {
+        let potion = "cool potion".to_string();
+
+        let pick_up = Ok(Command::PickUp {
+            item: potion.clone(),
+            count: None,
+        });
+
+        assert_eq!(parse("pick up Cool Potion"), pick_up);
+        assert_eq!(parse("get cool potion"), pick_up);
+        assert_eq!(parse("grab cool potion"), pick_up);
+        assert_eq!(parse("use cool potion"), Ok(Command::Use(potion.clone())));
+        assert_eq!(parse("unlock N with brass key"),
+                   Ok(Command::Unlock {
+                       what: Lockable::Exit(Direction::North),
+                       with: "brass key".to_string(),
+                   }));
+        assert_eq!(parse("unlock old chest with brass key"),
+                   Ok(Command::Unlock {
+                       what: Lockable::Item("old chest".to_string()),
+                       with: "brass key".to_string(),
+                   }));
+        assert_eq!(parse("open chest"), Ok(Command::Open("chest".to_string())));
+        assert_eq!(parse("shut chest"), Ok(Command::Close("chest".to_string())));
+        assert_eq!(parse("look inside chest"), Ok(Command::LookIn("chest".to_string())));
+        assert_eq!(parse("take cool potion from prudence"),
+                   Ok(Command::Take {
+                       item: potion,
+                       count: None,
+                       from: Some("prudence".to_string()),
+                   }));
+    }
+
+    #[test]
+    fn parses_quantities() {
+        assert_eq!(parse("pick up 2 potions"),
+                   Ok(Command::PickUp {
+                       item: "potions".to_string(),
+                       count: Some(2),
+                   }));
+        assert_eq!(parse("drop 1 potion"),
+                   Ok(Command::Drop {
+                       item: "potion".to_string(),
+                       count: Some(1),
+                   }));
+        assert_eq!(parse("take 3 coins from eve"),
+                   Ok(Command::Take {
+                       item: "coins".to_string(),
+                       count: Some(3),
+                       from: Some("eve".to_string()),
+                   }));
+        // numbers that aren't counts are part of the name
+        assert_eq!(parse("drop 0 potions"),
+                   Ok(Command::Drop {
+                       item: "0 potions".to_string(),
+                       count: None,
+                   }));
+        assert_eq!(parse("give 2 potions to Eve"),
+                   Ok(Command::Give {
+                       item: "potions".to_string(),
+                       count: Some(2),
+                       to: "eve".to_string(),
+                   }));
+        assert_eq!(parse("put hat into big bag"),
+                   Ok(Command::Put {
+                       item: "hat".to_string(),
+                       count: None,
+                       into: "big bag".to_string(),
+                   }));
+        assert_eq!(parse("trade 2 potions for hat with Eve"),
+                   Ok(Command::Trade {
+                       offer: "potions".to_string(),
+                       count: Some(2),
+                       item: "hat".to_string(),
+                       with: Some("eve".to_string()),
+                   }));
+        assert_eq!(parse("buy hat"),
+                   Ok(Command::Buy {
+                       item: "hat".to_string(),
+                       from: None,
+                   }));
+        assert_eq!(parse("give hat"),
+                   Err(ParseError::MissingObject { verb: "give hat to".to_string() }));
+        assert_eq!(parse("drop 2"),
+                   Ok(Command::Drop {
+                       item: "2".to_string(),
+                       count: None,
+                   }));
+    }
+
+    #[test]
+    fn parses_short_commands() {
+        assert_eq!(parse("i"), Ok(Command::Inventory));
+        assert_eq!(parse("print inventory"), Ok(Command::Inventory));
+        assert_eq!(parse("l"), Ok(Command::Look));
+        assert_eq!(parse("look around"), Ok(Command::Look));
+        assert_eq!(parse("display map"), Ok(Command::Map));
+        assert_eq!(parse("talk to George"), Ok(Command::Talk(Some("george".to_string()))));
+        assert_eq!(parse("q"), Ok(Command::Quit));
+        assert_eq!(parse("quests"), Ok(Command::Quests));
+        assert_eq!(parse("stats"), Ok(Command::Status));
+        assert_eq!(parse("restart"), Ok(Command::Restart));
+        assert_eq!(parse("undo"), Ok(Command::Undo));
+        assert_eq!(parse("attack"),
+                   Ok(Command::Attack {
+                       npc: None,
+                       with: None,
+                   }));
+        assert_eq!(parse("hit the goblin with a big stick"),
+                   Ok(Command::Attack {
+                       npc: Some("goblin".to_string()),
+                       with: Some("big stick".to_string()),
+                   }));
+    }
+
+    #[test]
+    fn reports_what_is_wrong() {
+        assert_eq!(parse("   "), Err(ParseError::Empty));
+        assert_eq!(parse("use"), Err(ParseError::MissingObject { verb: "use".to_string() }));
+        assert_eq!(parse("unlock north"),
+                   Err(ParseError::MissingObject { verb: "unlock north with".to_string() }));
+        assert_eq!(parse("go sideways"),
+                   Err(ParseError::UnknownDirection { dir: "sideways".to_string() }));
+        assert_eq!(parse("lok around"),
+                   Err(ParseError::UnknownVerb {
+                       verb: "lok".to_string(),
+                       suggestion: Some("look".to_string()),
+                   }));
+        assert_eq!(parse("tlak"),
+                   Err(ParseError::UnknownVerb {
+                       verb: "tlak".to_string(),
+                       suggestion: Some("talk".to_string()),
+                   }));
+        assert_eq!(parse("xyzzy"),
+                   Err(ParseError::UnknownVerb {
+                       verb: "xyzzy".to_string(),
+                       suggestion: None,
+                   }));
+        assert_eq!(parse("look at chest"),
+                   Err(ParseError::UnexpectedWords {
+                       verb: "look".to_string(),
+                       words: "at chest".to_string(),
+                   }));
+        assert_eq!(parse("show foo"),
+                   Err(ParseError::UnexpectedWords {
+                       verb: "show".to_string(),
+                       words: "foo".to_string(),
+                   }));
+        // a command is never its own suggestion
+        assert_ne!(suggest("look"), Some("look".to_string()));
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use effect::Effect;
+use player::Player;
+
+// A conversation an NPC can have with the player. It starts at the
+// `start` node; each node is something the NPC says, followed by the
+// responses the player can pick from. Picking a response carries out
+// its effects and moves on to the node it names, or ends the
+// conversation if it doesn't name one.
+//
+// In world files this goes alongside the NPC's `dialogue`:
+//
+//     "tree": {
+//       "start": "hello",
+//       "nodes": {
+//         "hello": {
+//           "line": "Want a hat?",
+//           "responses": [
+//             { "text": "Yes please!", "goto": "hat", "effects": [{ "set_flag": "hatted" }] },
+//             { "text": "No thanks." }
+//           ]
+//         },
+//         "hat": { "line": "Here you go." }
+//       }
+//     }
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DialogueTree {
+    pub start: String,
+    // a BTreeMap keeps the nodes in order in save files
+    pub nodes: BTreeMap<String, Node>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub line: String, // what the NPC says
+    // a node without responses (or without any the player is allowed
+    // to pick) ends the conversation
+    #[serde(default)]
+    pub responses: Vec<Response>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub text: String, // what the player says
+    #[serde(default)]
+    pub goto: Option<String>,
+    // the response is only offered if all of these hold
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+// Something that has to be true about the player for them to be
+// offered a response, like `{ "has_flag": "C00L d00d" }`.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    HasItem(String),
+    LacksItem(String),
+    HasFlag(String),
+    LacksFlag(String),
+}
+
+impl Condition {
+    pub fn holds(&self, player: &Player) -> bool {
+        let has_item = |name: &str| player.inventory().iter().any(|item| item.is_called(name));
+
+        match *self {
+            Condition::HasItem(ref name) => has_item(name),
+            Condition::LacksItem(ref name) => !has_item(name),
+            Condition::HasFlag(ref flag) => player.has_flag(flag),
+            Condition::LacksFlag(ref flag) => !player.has_flag(flag),
+        }
+    }
+}
+
+impl Node {
+    // The responses `player` can pick, in order. The player picks
+    // them by number, counting from 1.
+    pub fn responses_for(&self, player: &Player) -> Vec<&Response> {
+        self.responses
+            .iter()
+            .filter(|response| response.conditions.iter().all(|c| c.holds(player)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn only_offers_responses_whose_conditions_hold() {
+        let node: Node = serde_json::from_str(r#"{
+            "line": "hi",
+            "responses": [
+                { "text": "hello" },
+                { "text": "I'm cool", "conditions": [{ "has_flag": "C00L d00d" }] },
+                { "text": "got a hat?", "conditions": [{ "lacks_item": "hat" }] }
+            ]
+        }"#)
+            .unwrap();
+        let mut player = Player::new("Liz".to_string(), vec![], 0, 0);
+
+        let texts = |player: &Player| -> Vec<String> {
+            node.responses_for(player).iter().map(|r| r.text.clone()).collect()
+        };
+        assert_eq!(texts(&player), vec!["hello", "got a hat?"]);
+
+        player.flags.insert("C00L d00d".to_string());
+        assert_eq!(texts(&player), vec!["hello", "I'm cool", "got a hat?"]);
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
+// The ways a player can leave a room. Rooms list which of these they
+// have exits for (see Room::exits), so not every room has every one.
+//
+// In world files these are written in lowercase, like "north".
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+    Up,
+    Down,
+    In,
+    Out,
+}
+
+impl Direction {
+    // Every direction, in the order we list them to the player.
+    pub fn all() -> [Direction; 8] {
+        [Direction::North,
+         Direction::South,
+         Direction::East,
+         Direction::West,
+         Direction::Up,
+         Direction::Down,
+         Direction::In,
+         Direction::Out]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+
+    // The way back, like south for north.
+    pub fn opposite(&self) -> Direction {
+        match *self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::In => Direction::Out,
+            Direction::Out => Direction::In,
+        }
+    }
+
+    // The one letter version of the name, if it has one. "in" and
+    // "out" don't, since "i" already means "inventory".
+    pub fn short_name(&self) -> Option<&'static str> {
+        match *self {
+            Direction::North => Some("n"),
+            Direction::South => Some("s"),
+            Direction::East => Some("e"),
+            Direction::West => Some("w"),
+            Direction::Up => Some("u"),
+            Direction::Down => Some("d"),
+            Direction::In | Direction::Out => None,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Parses "north" or just "n", in any case.
+impl FromStr for Direction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Direction, ()> {
+        let s = s.to_lowercase();
+
+        Direction::all()
+            .iter()
+            .find(|dir| s == dir.name() || Some(&s[..]) == dir.short_name())
+            .cloned()
+            .ok_or(())
+    }
+}
@@ -0,0 +1,149 @@
+use std::cmp::min;
+use direction::Direction;
+use event::Event;
+use inventory_item::{self, InventoryItem};
+use location::Location;
+use map::Map;
+use player::Player;
+use rng::Rng;
+
+// Something that happens when the player uses an item, or picks a
+// response in a conversation (see dialogue.rs). Items list these in
+// their `actions`, and `apply` carries them out.
+//
+// In world files these look like `{ "heal": 10 }` or
+// `{ "set_flag": "C00L d00d" }`. Rooms can have them too, as hazards
+// (see room::Hazard).
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    // give the player back some health
+    Heal(u64),
+    // take some away, which can kill the player
+    Damage(u64),
+    // add to (or, if `by` is negative, take away from) one of the
+    // player's stats, like `{ "change_stat": { "stat": "coolness", "by": 5 } }`
+    ChangeStat { stat: String, by: i64 },
+    // mark the player with a flag, like "C00L d00d"
+    SetFlag(String),
+    // move the player somewhere else in the map
+    Teleport(Location),
+    // make a hidden exit in `room` visible and usable
+    RevealExit { room: Location, dir: Direction },
+    // drop a new item into the room the player is in
+    SpawnItem(InventoryItem),
+    // give an NPC (wherever they are) something new to say
+    SetDialogue { npc: String, dialogue: String },
+    // put a new item straight into the player's inventory
+    GiveItem(InventoryItem),
+    // unlock an exit (and the other side of it), without a key
+    UnlockExit { room: Location, dir: Direction },
+    // do one of these, picked at random
+    OneOf(Vec<Effect>),
+    // show the player the whole map, not just where they've been
+    // (see Player::map_revealed). In world files this is just
+    // "reveal_map".
+    RevealMap,
+}
+
+// Carry out `effect` and report what happened. Anything random comes
+// from `rng`, so that it's the same every time for the same seed.
+pub fn apply(effect: &Effect, player: &mut Player, map: &mut Map, rng: &mut Rng) -> Event {
+    match *effect {
+        Effect::Heal(amount) => {
+            player.health = min(player.health.saturating_add(amount), player.max_health);
+            Event::Healed {
+                amount: amount,
+                health: player.health,
+            }
+        }
+        Effect::Damage(amount) => {
+            player.health = player.health.saturating_sub(amount);
+            Event::Hurt {
+                amount: amount,
+                health: player.health,
+            }
+        }
+        Effect::ChangeStat { ref stat, by } => {
+            let value = player.stats.entry(stat.clone()).or_insert(0);
+            *value = value.saturating_add(by);
+            Event::StatChanged {
+                stat: stat.clone(),
+                by: by,
+                value: *value,
+            }
+        }
+        Effect::SetFlag(ref flag) => {
+            player.flags.insert(flag.clone());
+            Event::FlagSet { flag: flag.clone() }
+        }
+        Effect::Teleport(ref to) => {
+            if map.rooms.contains_key(to) {
+                player.location = to.clone();
+                Event::Teleported { to: to.clone() }
+            } else {
+                Event::NothingHappened
+            }
+        }
+        Effect::RevealExit { ref room, dir } => {
+            match map.rooms.get_mut(room).and_then(|room| room.exits.get_mut(&dir)) {
+                Some(exit) => {
+                    exit.hidden = false;
+                    Event::ExitRevealed {
+                        room: room.clone(),
+                        dir: dir,
+                    }
+                }
+                None => Event::NothingHappened,
+            }
+        }
+        Effect::SpawnItem(ref item) => {
+            match map.rooms.get_mut(&player.location) {
+                Some(room) => {
+                    inventory_item::add(&mut room.items, item.clone());
+                    Event::ItemSpawned { item: item.name.clone() }
+                }
+                None => Event::NothingHappened,
+            }
+        }
+        Effect::SetDialogue { ref npc, ref dialogue } => {
+            let mut npcs = map.rooms.values_mut().flat_map(|room| room.npcs.iter_mut());
+            match npcs.find(|n| n.name == *npc) {
+                Some(found) => {
+                    found.dialogue = dialogue.clone();
+                    Event::DialogueChanged { npc: npc.clone() }
+                }
+                None => Event::NothingHappened,
+            }
+        }
+        Effect::GiveItem(ref item) => {
+            player.add_to_inventory(item.clone());
+            Event::ItemReceived {
+                item: item.name.clone(),
+                count: item.count(),
+            }
+        }
+        Effect::UnlockExit { ref room, dir } => {
+            if map.unlock(room, dir) {
+                Event::ExitUnlocked {
+                    room: room.clone(),
+                    dir: dir,
+                }
+            } else {
+                Event::NothingHappened
+            }
+        }
+        Effect::OneOf(ref effects) => {
+            if effects.is_empty() {
+                Event::NothingHappened
+            } else {
+                let i = rng.below(effects.len() as u64) as usize;
+                apply(&effects[i], player, map, rng)
+            }
+        }
+        Effect::RevealMap => {
+            player.map_revealed = true;
+            Event::MapRevealed
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use command::{Lockable, ParseError};
+use direction::Direction;
+use inventory_item::InventoryItem;
+use location::Location;
+use npc::NPC;
+use quest::{Objective, Quest};
+
+// Somewhere an item can be: on the floor of the current room, with
+// the room's NPC, with the player, or inside another item.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Container {
+    Room,
+    Npc(String), // the NPC's name
+    Player,
+    Item(String), // the name of the item it's in
+}
+
+// What happened as the result of a command. Game doesn't print
+// anything itself; it hands these to a renderer (see render.rs),
+// which decides what to show the player.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Event {
+    Welcome { player: String, title: String },
+    Help,
+    Quit,
+    CommandNotUnderstood { error: ParseError },
+
+    // MOVING //
+    Moved {
+        dir: Direction,
+        from: Location,
+        to: Location,
+    },
+    BlockedDirection { dir: Direction },
+    ExitLocked { dir: Direction },
+    Unlocked {
+        what: Lockable,
+        item: String,
+        consumed: bool, // whether the key was used up
+    },
+    NotLocked { what: Lockable },
+    WrongKey { what: Lockable, item: String },
+
+    // ITEMS //
+    ItemPickedUp { item: String, count: u64 },
+    ItemTaken {
+        item: String,
+        count: u64,
+        npc: String,
+    },
+    ItemDropped { item: String, count: u64 },
+    ItemGiven {
+        item: String,
+        count: u64,
+        npc: String,
+    },
+    ItemPut {
+        item: String,
+        count: u64,
+        container: String,
+    },
+    ItemUsed { item: String, effects: String },
+    ItemNotFound { name: String, container: Container },
+    // for commands like `open` that look in the room and the
+    // player's inventory
+    ItemNotNearby { name: String },
+    // the player asked for more of an item than there is
+    NotEnough {
+        name: String,
+        container: Container,
+        have: u64,
+    },
+    NotAContainer { name: String },
+    ItemInItself { item: String },
+
+    // TRADING //
+    Traded {
+        npc: String,
+        gave: String,
+        gave_count: u64,
+        got: String,
+    },
+    // the NPC doesn't trade `item`, or not with this player
+    TradeRefused { npc: String, item: String },
+    // what the NPC wants for `item`, when the player tries to take it
+    // or offers the wrong thing for it
+    TradeWants {
+        npc: String,
+        item: String,
+        wants: String,
+        price: u64,
+    },
+
+    // CONTAINERS //
+    Opened {
+        item: String,
+        contents: Vec<InventoryItem>,
+    },
+    Closed { item: String },
+    AlreadyOpen { item: String },
+    AlreadyClosed { item: String },
+    ContainerLocked { item: String },
+    // the player tried to get at what's inside something closed
+    ContainerClosed { item: String },
+
+    // EFFECTS //
+    Healed { amount: u64, health: u64 },
+    Hurt { amount: u64, health: u64 },
+    StatChanged {
+        stat: String,
+        by: i64,
+        value: i64, // what it is now
+    },
+    FlagSet { flag: String },
+    Teleported { to: Location },
+    ExitRevealed { room: Location, dir: Direction },
+    ItemSpawned { item: String },
+    ItemReceived { item: String, count: u64 },
+    ExitUnlocked { room: Location, dir: Direction },
+    DialogueChanged { npc: String },
+    MapRevealed,
+    NothingHappened,
+
+    NpcNotFound { name: String },
+    // an NPC came into or left the player's room. `dir` is the way
+    // they came from or went, if they used an exit; otherwise they
+    // just showed up or went away (see npc::Schedule).
+    NpcArrived { npc: String, dir: Option<Direction> },
+    NpcLeft { npc: String, dir: Option<Direction> },
+    // the player didn't say which NPC, and there's nobody to pick...
+    NoOneHere,
+    // ...or more than one
+    WhichNpc { npcs: Vec<String> },
+
+    // FIGHTING //
+    // the player hit an NPC, who has `health` left
+    Attacked {
+        npc: String,
+        with: Option<String>, // the weapon, if they used one
+        damage: u64,
+        health: u64,
+    },
+    NpcKilled {
+        npc: String,
+        dropped: Vec<InventoryItem>, // what they had, now on the floor
+    },
+    // an NPC hit the player, who has `health` left
+    NpcAttacked {
+        npc: String,
+        damage: u64,
+        health: u64,
+    },
+    WontFight { npc: String },
+    NotAWeapon { item: String },
+    // the player left a room with hostile NPCs in it
+    Fled { npcs: Vec<String> },
+
+    // LOOKING //
+    LookedAround {
+        description: String,
+        exits: Vec<Direction>,
+        locked: Vec<Direction>, // the exits that are locked
+        items: Vec<InventoryItem>,
+        npcs: Vec<NPC>,
+    },
+    LookedIn {
+        item: String,
+        contents: Vec<InventoryItem>,
+    },
+    Talked { npc: String, dialogue: String },
+
+    // CONVERSATIONS //
+    // an NPC with a dialogue tree said something; `responses` are
+    // what the player can say back, and if there are none the
+    // conversation is over
+    Said {
+        npc: String,
+        line: String,
+        responses: Vec<String>,
+    },
+    ConversationEnded { npc: String },
+    // the player didn't pick one of the numbered responses
+    NotAResponse { responses: usize },
+    MapShown { map: String }, // see Map::draw
+    InventoryShown { items: Vec<InventoryItem> },
+    StatusShown {
+        health: u64,
+        max_health: u64,
+        stats: BTreeMap<String, i64>,
+    },
+
+    // STAYING ALIVE //
+    // the room the player is in did something to them (see
+    // room::Hazard); what it did follows as more events
+    Hazard { description: String },
+    Died,
+    // the player tried to do something after dying
+    GameIsOver,
+    Restarted,
+
+    // UNDO //
+    // `input` is the command that was undone or redone
+    Undone { input: String },
+    Redone { input: String },
+    NothingToUndo,
+    NothingToRedo,
+
+    // QUESTS //
+    QuestsShown { quests: Vec<Quest> },
+    ObjectiveDone { quest: String, objective: Objective },
+    QuestDone { quest: String },
+    // the player met the world's win condition, which ends the game
+    Won { quests: Vec<String>, turns: u64 },
+
+    // SAVING //
+    Saved { slot: String },
+    SaveFailed { slot: String, reason: String },
+    Loaded { slot: String },
+    LoadFailed { slot: String, reason: String },
+}
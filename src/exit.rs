@@ -0,0 +1,115 @@
+use location::Location;
+
+// A way out of a room, and where it leads. Exits can be locked, in
+// which case the player needs to unlock them with the right key
+// before they can go through.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(from = "ExitFile")]
+pub struct Exit {
+    pub to: Location,
+    pub lock: Option<Lock>,
+    // hidden exits can't be seen or used until something reveals
+    // them (see Effect::RevealExit)
+    pub hidden: bool,
+}
+
+// What it takes to get through a locked exit.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Lock {
+    pub key: String, // the name of the item that opens it
+    // whether the key is used up when it opens the lock
+    #[serde(default)]
+    pub consume: bool,
+    #[serde(default = "default_locked")]
+    pub locked: bool,
+}
+
+impl Exit {
+    pub fn new(to: Location) -> Exit {
+        Exit {
+            to: to,
+            lock: None,
+            hidden: false,
+        }
+    }
+
+    pub fn locked(to: Location, key: &str) -> Exit {
+        Exit {
+            to: to,
+            lock: Some(Lock {
+                key: key.to_string(),
+                consume: false,
+                locked: true,
+            }),
+            hidden: false,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.lock.as_ref().map(|lock| lock.locked).unwrap_or(false)
+    }
+}
+
+// In a world file an exit is usually just where it goes, like
+// `"north": { "x": 0, "y": 1 }`, but it can also be written out in
+// full to give it a lock:
+//
+//     "north": { "to": { "x": 0, "y": 1 }, "lock": { "key": "brass key" } }
+//
+// or to hide it:
+//
+//     "down": { "to": { "x": 2, "y": 2 }, "hidden": true }
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExitFile {
+    Plain(Location),
+    Full {
+        to: Location,
+        #[serde(default)]
+        lock: Option<Lock>,
+        #[serde(default)]
+        hidden: bool,
+    },
+}
+
+impl From<ExitFile> for Exit {
+    fn from(file: ExitFile) -> Exit {
+        match file {
+            ExitFile::Plain(to) => Exit::new(to),
+            ExitFile::Full { to, lock, hidden } => {
+                Exit {
+                    to: to,
+                    lock: lock,
+                    hidden: hidden,
+                }
+            }
+        }
+    }
+}
+
+// Locks in world files start out locked unless they say otherwise.
+fn default_locked() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn reads_plain_and_full_exits() {
+        let plain: Exit = serde_json::from_str(r#"{ "x": 0, "y": 1 }"#).unwrap();
+        assert_eq!(plain, Exit::new(Location::new(0, 1)));
+
+        let locked: Exit =
+            serde_json::from_str(r#"{ "to": { "x": 0, "y": 1 }, "lock": { "key": "brass key" } }"#)
+                .unwrap();
+        assert_eq!(locked, Exit::locked(Location::new(0, 1), "brass key"));
+
+        let hidden: Exit = serde_json::from_str(r#"{ "to": { "x": 2, "y": 2 }, "hidden": true }"#)
+            .unwrap();
+        assert!(hidden.hidden);
+        assert!(!hidden.is_locked());
+    }
+}
@@ -1,38 +1,185 @@
-extern crate regex;
-
 use player::Player;
 use map::Map;
+use location::Location;
+use npc::{Behaviour, NPC};
 use room::Room;
-use inventory_item::InventoryItem;
-use std::io;
+use inventory_item::{self, called, InventoryItem, Missing};
+use command::{self, Command, Lockable};
+use dialogue::Node;
+use direction::Direction;
+use effect;
+use event::{Container, Event};
+use render;
+use rng::Rng;
+use save::{self, SaveFile};
+use transcript::Step;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+// The most damage the player does without a weapon.
+const FIST_DAMAGE: u64 = 2;
+
+// How many commands `undo` can take back.
+const UNDO_LIMIT: usize = 20;
 
 // where most player console interactions and game loop will be defined
+//
+// A Game reads commands from `input` and writes everything it has to
+// say to `output`. In main() those are stdin and stdout, but anything
+// that implements BufRead and Write works, like a byte slice and a
+// Vec<u8> in tests.
+//
+// While the player is talking to an NPC with a dialogue tree (see
+// dialogue.rs), their input picks a response instead of being parsed
+// as a command, until the conversation ends or they say "bye".
+//
+// When the player's health runs out it's game over, and the only
+// commands left are restart, load, undo and quit.
+//
+// Before every command that changes anything, the game keeps a
+// Snapshot of itself, so that `undo` can put things back the way they
+// were. Saving, loading and quitting can't be undone, and loading or
+// restarting forgets everything there was to undo.
+//
+// The commands themselves don't print anything. They return Events,
+// which `play` turns into text with render::text. Frontends that want
+// to show things differently can call `execute` and render the events
+// however they like.
 #[derive(Debug, Eq, PartialEq)]
-pub struct Game {
+pub struct Game<R, W> {
     player: Player,
     map: Map,
     pub playing: bool,
+    pub save_dir: PathBuf, // where `save <slot>` puts its files
+    // how many turns have gone by (see `tick`)
+    pub turn: u64,
+    // decides where wandering NPCs go, and how hard everyone hits
+    pub rng: Rng,
+    talking: Option<Talking>,
+    pub game_over: bool, // the player died, and hasn't restarted yet
+    // the player and map as they were at the start, for `restart`
+    beginning: (Player, Map),
+    // every step played so far that hasn't been written to a
+    // transcript yet, if we're recording one (see transcript.rs)
+    pub recording: Option<Vec<Step>>,
+    // the most recent snapshot is at the back
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    input: R,
+    output: W,
 }
 
-impl Game {
-    fn start(player: Player, map: Map, playing: bool) {
-        let mut game = Game::new(player, map, playing);
-        game.play();
-    }
+// Everything about the game that a command can change, and the
+// command (as the player typed it) that's undone or redone by going
+// back to it.
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Snapshot {
+    input: String,
+    player: Player,
+    map: Map,
+    turn: u64,
+    rng: Rng,
+    talking: Option<Talking>,
+    game_over: bool,
+}
 
-    pub fn new(player: Player, map: Map, playing: bool) -> Game {
-        println!("Hi {}Welcome to {}", player.name, map.title);
+// Who the player is talking to, and where they are in the
+// conversation.
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Talking {
+    npc: String,
+    node: String,
+}
+
+impl<R: BufRead, W: Write> Game<R, W> {
+    fn start(player: Player, map: Map, playing: bool, input: R, output: W) -> io::Result<()> {
+        let mut game = Game::new(player, map, playing, input, output);
+        game.run()
+    }
 
-        Game {
+    pub fn new(player: Player, map: Map, playing: bool, input: R, output: W) -> Game<R, W> {
+        let beginning = (player.clone(), map.clone());
+        let mut game = Game {
             player: player,
             map: map,
             playing: playing,
+            save_dir: PathBuf::from("saves"),
+            turn: 0,
+            rng: Rng::from_time(),
+            talking: None,
+            game_over: false,
+            beginning: beginning,
+            recording: None,
+            undo: VecDeque::new(),
+            redo: vec![],
+            input: input,
+            output: output,
+        };
+
+        // send away anyone who isn't around at the start
+        game.follow_schedules();
+        game
+    }
+
+    pub fn welcome(&mut self) -> io::Result<()> {
+        let event = Event::Welcome {
+            player: self.player.name.clone(),
+            title: self.map.title.clone(),
+        };
+        self.render(&[event])
+    }
+
+    // Play until the player exits or the input runs out.
+    pub fn run(&mut self) -> io::Result<()> {
+        self.welcome()?;
+
+        while self.playing {
+            self.play()?;
+        }
+
+        Ok(())
+    }
+
+    // Ask for one command, run it and show what happened.
+    pub fn play(&mut self) -> io::Result<()> {
+        let prompt = if self.game_over {
+            render::GAME_OVER_PROMPT
+        } else if self.talking.is_some() {
+            render::TALK_PROMPT
+        } else {
+            render::PROMPT
+        };
+        writeln!(self.output, "{}", prompt)?;
+
+        let mut user_input = String::new();
+
+        // read_line reads nothing once the input has run out (like
+        // at the end of a script), so there is nothing left to do.
+        if self.input.read_line(&mut user_input)? == 0 {
+            self.playing = false;
+            return Ok(());
+        }
+
+        let events = self.execute(&user_input);
+        if let Some(ref mut recording) = self.recording {
+            recording.push(Step {
+                input: user_input.trim_end_matches(&['\r', '\n'][..]).to_string(),
+                output: render::all(&events),
+            });
+        }
+        self.render(&events)
+    }
+
+    fn render(&mut self, events: &[Event]) -> io::Result<()> {
+        for event in events {
+            writeln!(self.output, "{}", render::text(event))?;
         }
+        Ok(())
     }
 
-    pub fn play(&mut self) {
-        println!("What would you like to do? (enter 'help' to see a list of commands)");
-        self.parse_choice();
+    pub fn output(&self) -> &W {
+        &self.output
     }
 
     fn current_room(&self) -> &Room {
@@ -57,164 +204,1955 @@ impl Game {
         &mut self.player
     }
 
-    fn parse_choice(&mut self) {
-        let mut user_input = String::new();
+    // Run a single line of player input and report what happened.
+    pub fn execute(&mut self, user_input: &str) -> Vec<Event> {
+        let mut events = if self.talking.is_some() {
+            self.remember(user_input);
+            let talking = self.talking.take().expect("BUG: checked above");
+            self.respond(talking, user_input)
+        } else {
+            match command::parse(user_input) {
+                Ok(command) if self.game_over && !command.works_after_game_over() => {
+                    vec![Event::GameIsOver]
+                }
+                Ok(command) => {
+                    // anything that takes time can be undone
+                    let takes_time = command.takes_time();
+                    if takes_time {
+                        self.remember(user_input);
+                    }
+                    let player_was = self.player.location.clone();
+                    let mut events = self.perform(command);
+
+                    if takes_time && self.playing {
+                        events.extend(self.tick(&player_was));
+                    }
+                    events
+                }
+                Err(error) => vec![Event::CommandNotUnderstood { error: error }],
+            }
+        };
+        self.player.visited.insert(self.player.location.clone());
+
+        let progress = self.track_quests(&events);
+        events.extend(progress);
+
+        if self.player.is_dead() && !self.game_over {
+            self.game_over = true;
+            self.talking = None;
+            events.push(Event::Died);
+        }
+        events
+    }
+
+    // UNDO //
+
+    fn snapshot(&self, input: String) -> Snapshot {
+        Snapshot {
+            input: input,
+            player: self.player.clone(),
+            map: self.map.clone(),
+            turn: self.turn,
+            rng: self.rng.clone(),
+            talking: self.talking.clone(),
+            game_over: self.game_over,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.player = snapshot.player;
+        self.map = snapshot.map;
+        self.turn = snapshot.turn;
+        self.rng = snapshot.rng;
+        self.talking = snapshot.talking;
+        self.game_over = snapshot.game_over;
+    }
+
+    // Keep the game as it is before running `user_input`, so that it
+    // can be undone. Doing something new means there's nothing to redo.
+    fn remember(&mut self, user_input: &str) {
+        let snapshot = self.snapshot(user_input.trim().to_string());
+        self.undo.push_back(snapshot);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    fn forget(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) -> Event {
+        match self.undo.pop_back() {
+            Some(snapshot) => {
+                let input = snapshot.input.clone();
+                let now = self.snapshot(input.clone());
+                self.redo.push(now);
+                self.restore(snapshot);
+                Event::Undone { input: input }
+            }
+            None => Event::NothingToUndo,
+        }
+    }
+
+    fn redo(&mut self) -> Event {
+        match self.redo.pop() {
+            Some(snapshot) => {
+                let input = snapshot.input.clone();
+                let now = self.snapshot(input.clone());
+                self.undo.push_back(now);
+                self.restore(snapshot);
+                Event::Redone { input: input }
+            }
+            None => Event::NothingToRedo,
+        }
+    }
+
+    // THE CLOCK //
+
+    // Move the clock on one turn, after the player does something,
+    // and let every NPC do whatever they do. `player_was` is where the
+    // player was before they did it, so that NPCs can follow them.
+    fn tick(&mut self, player_was: &Location) -> Vec<Event> {
+        self.turn += 1;
+
+        let mut events = self.follow_schedules();
+        let here = self.player.location.clone();
+
+        // Work out where everyone is going before moving anyone, so
+        // that nobody moves twice in one turn. Rooms (and exits) are
+        // gone through in order, so that the same seed always sends
+        // wanderers the same way.
+        let mut locations: Vec<Location> = self.map.rooms.keys().cloned().collect();
+        locations.sort();
+
+        let mut moves = vec![];
+        for from in locations {
+            for npc in self.map.rooms[&from].npcs() {
+                let to = match npc.behaviour {
+                    Behaviour::Stay => None,
+                    Behaviour::Wander => {
+                        // only half the time, so they don't seem to
+                        // be in a hurry
+                        let exits: Vec<Location> = self.map
+                            .valid_directions(&from)
+                            .into_iter()
+                            .filter_map(|dir| self.map.exit(&from, dir))
+                            .filter(|exit| !exit.is_locked())
+                            .filter(|exit| self.map.rooms.contains_key(&exit.to))
+                            .map(|exit| exit.to.clone())
+                            .collect();
+
+                        if !exits.is_empty() && self.rng.below(2) == 0 {
+                            Some(exits[self.rng.below(exits.len() as u64) as usize].clone())
+                        } else {
+                            None
+                        }
+                    }
+                    Behaviour::Patrol(ref route) => {
+                        let next = match route.iter().position(|l| *l == from) {
+                            Some(i) => route.get((i + 1) % route.len()),
+                            None => route.first(),
+                        };
+                        next.filter(|to| self.map.rooms.contains_key(to)).cloned()
+                    }
+                    Behaviour::Follow if from == *player_was && here != *player_was => {
+                        Some(here.clone())
+                    }
+                    Behaviour::Follow => None,
+                };
+
+                if let Some(to) = to {
+                    moves.push((from.clone(), npc.name.clone(), to));
+                }
+            }
+        }
+
+        for (from, name, to) in moves {
+            if from == to {
+                continue;
+            }
+
+            let npc = {
+                let room = self.map.rooms.get_mut(&from).expect("BUG: the NPC's room is gone");
+                let i = room.npcs
+                    .iter()
+                    .position(|npc| npc.name == name)
+                    .expect("BUG: the NPC is gone");
+                room.npcs.remove(i)
+            };
+            self.map.rooms.get_mut(&to).expect("BUG: checked above").npcs.push(npc);
+
+            if from == here {
+                events.push(Event::NpcLeft {
+                    npc: name,
+                    dir: self.map.direction_between(&from, &to),
+                });
+            } else if to == here {
+                events.push(Event::NpcArrived {
+                    npc: name,
+                    dir: self.map.direction_between(&to, &from),
+                });
+            }
+        }
+
+        events.extend(self.hostile_attacks());
+        events.extend(self.hazard());
+        events
+    }
+
+    // Every hostile NPC in the player's room has a go at them.
+    fn hostile_attacks(&mut self) -> Vec<Event> {
+        let attackers: Vec<(String, u64)> = self.current_room()
+            .npcs()
+            .into_iter()
+            .filter(|npc| npc.is_hostile())
+            .filter_map(|npc| npc.fight.as_ref().map(|fight| (npc.name.clone(), fight.attack)))
+            .filter(|&(_, attack)| attack > 0)
+            .collect();
+        let mut events = vec![];
+
+        for (npc, attack) in attackers {
+            if self.player.is_dead() {
+                break;
+            }
+
+            let damage = 1 + self.rng.below(attack);
+            self.player.health = self.player.health.saturating_sub(damage);
+            events.push(Event::NpcAttacked {
+                npc: npc,
+                damage: damage,
+                health: self.player.health,
+            });
+        }
+
+        events
+    }
+
+    // Whatever the room the player is in does to them at the end of
+    // a turn (see room::Hazard).
+    fn hazard(&mut self) -> Vec<Event> {
+        let hazard = match self.current_room().hazard {
+            Some(ref hazard) => hazard.clone(),
+            None => return vec![],
+        };
+
+        let mut events = vec![Event::Hazard { description: hazard.description }];
+        for effect in &hazard.effects {
+            events.push(effect::apply(effect, &mut self.player, &mut self.map, &mut self.rng));
+        }
+        events
+    }
+
+    // Send NPCs away, or bring them back, depending on what turn it
+    // is, and report any that come or go from the player's room.
+    fn follow_schedules(&mut self) -> Vec<Event> {
+        let turn = self.turn;
+        let here = self.player.location.clone();
+        let mut events = vec![];
+
+        for room in self.map.rooms.values_mut() {
+            for npc in &mut room.npcs {
+                let away = match npc.schedule {
+                    Some(ref schedule) => !schedule.includes(turn),
+                    None => continue,
+                };
+
+                if away != npc.away {
+                    npc.away = away;
+
+                    if room.location == here {
+                        let name = npc.name.clone();
+                        events.push(if away {
+                            Event::NpcLeft {
+                                npc: name,
+                                dir: None,
+                            }
+                        } else {
+                            Event::NpcArrived {
+                                npc: name,
+                                dir: None,
+                            }
+                        });
+                    }
+                }
+            }
+        }
+
+        events
+    }
+
+    // QUESTS //
+
+    // Tick off every quest objective that `events` did, and end the
+    // game if that means the player has won.
+    fn track_quests(&mut self, events: &[Event]) -> Vec<Event> {
+        let done: Vec<(usize, usize)> = {
+            let (player, map) = (&self.player, &self.map);
+            map.quests
+                .iter()
+                .enumerate()
+                .flat_map(|(q, quest)| {
+                    quest.objectives
+                        .iter()
+                        .enumerate()
+                        .filter(move |&(o, _)| !quest.done.contains(&o))
+                        .filter(|&(_, objective)| {
+                            events.iter().any(|event| objective.done_by(event, player, map))
+                        })
+                        .map(move |(o, _)| (q, o))
+                })
+                .collect()
+        };
+
+        if done.is_empty() {
+            return vec![];
+        }
 
-        io::stdin()
-            .read_line(&mut user_input)
-            .expect("Could not read line");
-
-        let user_input = user_input.trim();
-
-        if user_input == "look around" {
-            self.look_around();
-        } else if user_input == "talk" {
-            self.talk();
-        } else if user_input == "display map" {
-            self.map.display_map();
-        } else if user_input == "print inventory" {
-            self.player.print_inventory();
-        } else if user_input == "exit" {
-            println!("Thanks for playing!");
+        let mut progress = vec![];
+        for &(q, o) in &done {
+            let quest = &mut self.map.quests[q];
+            quest.done.insert(o);
+            progress.push(Event::ObjectiveDone {
+                quest: quest.name.clone(),
+                objective: quest.objectives[o].clone(),
+            });
+
+            if quest.is_complete() {
+                progress.push(Event::QuestDone { quest: quest.name.clone() });
+            }
+        }
+
+        let won = self.map.win.as_ref().map(|win| win.holds(&self.map.quests)).unwrap_or(false);
+        if won {
             self.playing = false;
-        } else if user_input == "help" {
-            println!("exit: exit the game");
-            println!("move north, south, east, west: move in this direction");
-            println!("look around: see a description of the current room");
-            println!("pick up _item_: add the item to your inventory");
-            println!("take _item_: take an item from an NPC");
-            println!("use _item_: use an item in your inventory");
-            println!("talk: talk to an NPC");
-            println!("display map: look at map");
-            println!("print inventory: show current player inventory");
-        } else if let Some(captures) = regex("(?i)^pick up (?P<thing>.*)").captures(user_input) {
-            self.pick_up(captures.name("thing").expect("unexpected optional capture").as_str());
-        } else if let Some(captures) = regex("(?i)^take (?P<thing>.*)").captures(user_input) {
-            self.take(captures.name("thing").expect("unexpected optional capture").as_str());
-        } else if let Some(captures) = regex("(?i)^use (?P<thing>.*)").captures(user_input) {
-            self.use_item(captures.name("thing").expect("unexpected optional capture").as_str());
-        } else if let Some(captures) = regex("(?i)^move (?P<direction>.*)").captures(user_input) {
-            self.change_location(captures.name("direction")
-                .expect("unexpected optional capture")
-                .as_str());
-        } else {
-            println!("No such command: {:?}. Sorry!", user_input);
+            self.talking = None;
+            progress.push(Event::Won {
+                quests: self.map
+                    .quests
+                    .iter()
+                    .filter(|quest| quest.is_complete())
+                    .map(|quest| quest.name.clone())
+                    .collect(),
+                turns: self.turn,
+            });
+        }
+
+        progress
+    }
+
+    pub fn perform(&mut self, command: Command) -> Vec<Event> {
+        match command {
+            Command::Move(dir) => self.go(dir),
+            Command::PickUp { item, count } => vec![self.pick_up(&item, count)],
+            Command::Take { item, count, from } => {
+                vec![self.take(&item, count, from.as_ref().map(|s| &s[..]))]
+            }
+            Command::Drop { item, count } => vec![self.drop(&item, count)],
+            Command::Give { item, count, to } => vec![self.give(&item, count, &to)],
+            Command::Put { item, count, into } => vec![self.put(&item, count, &into)],
+            Command::Trade { offer, count, item, with } => {
+                vec![self.trade(Some(&offer), count, &item, with.as_ref().map(|s| &s[..]))]
+            }
+            Command::Buy { item, from } => {
+                vec![self.trade(None, None, &item, from.as_ref().map(|s| &s[..]))]
+            }
+            Command::Use(item) => self.use_item(&item),
+            Command::Unlock { what, with } => vec![self.unlock(what, &with)],
+            Command::Open(item) => vec![self.open(&item)],
+            Command::Close(item) => vec![self.close(&item)],
+            Command::Look => vec![self.look_around()],
+            Command::LookIn(item) => vec![self.look_in(&item)],
+            Command::Talk(npc) => self.talk(npc.as_ref().map(|s| &s[..])),
+            Command::Attack { npc, with } => {
+                self.attack(npc.as_ref().map(|s| &s[..]), with.as_ref().map(|s| &s[..]))
+            }
+            Command::Map => vec![self.display_map()],
+            Command::Inventory => {
+                vec![Event::InventoryShown { items: self.player.inventory().to_vec() }]
+            }
+            Command::Quests => vec![Event::QuestsShown { quests: self.map.quests.clone() }],
+            Command::Status => {
+                vec![Event::StatusShown {
+                         health: self.player.health,
+                         max_health: self.player.max_health,
+                         stats: self.player.stats.clone(),
+                     }]
+            }
+            Command::Help => vec![Event::Help],
+            Command::Quit => {
+                self.playing = false;
+                vec![Event::Quit]
+            }
+            Command::Save(slot) => vec![self.save(&slot)],
+            Command::Load(slot) => vec![self.load(&slot)],
+            Command::Restart => vec![self.restart()],
+            Command::Undo => vec![self.undo()],
+            Command::Redo => vec![self.redo()],
         }
     }
 
     // MOVES //
 
-    fn string_to_inventory_item(&mut self, item: &str) -> Option<InventoryItem> {
-        let items = self.current_room_mut().items_mut();
+    // The items in `container`, if there is such a container here.
+    fn items_mut(&mut self, container: &Container) -> Option<&mut Vec<InventoryItem>> {
+        let room = self.map
+            .rooms
+            .get_mut(&self.player.location)
+            .expect("BUG: The player's location must exist in the map");
+
+        match *container {
+            Container::Room => Some(room.items_mut()),
+            Container::Player => Some(self.player.inventory_mut()),
+            Container::Npc(ref name) => room.npc_mut(name).map(|npc| npc.inventory_mut()),
+            // something the player is carrying, or that's lying around
+            Container::Item(ref name) => {
+                self.player
+                    .inventory_mut()
+                    .iter_mut()
+                    .chain(room.items.iter_mut())
+                    .find(|item| item.is_called(name))
+                    .and_then(|item| item.open_contents_mut())
+            }
+        }
+    }
+
+    // The item called `name` that the player is carrying, or else
+    // that's lying around in this room.
+    fn find_item(&self, name: &str) -> Option<&InventoryItem> {
+        self.player
+            .inventory()
+            .iter()
+            .chain(self.current_room().items())
+            .find(|item| item.is_called(name))
+    }
+
+    fn find_item_mut(&mut self, name: &str) -> Option<&mut InventoryItem> {
+        let room = self.map
+            .rooms
+            .get_mut(&self.player.location)
+            .expect("BUG: The player's location must exist in the map");
 
-        items.iter()
-            .position(|thing| item == thing.name)
-            .map(|i| items.remove(i))
+        self.player
+            .inventory_mut()
+            .iter_mut()
+            .chain(room.items.iter_mut())
+            .find(|item| item.is_called(name))
     }
 
-    fn string_to_npc_item(&mut self, item_name: &str) -> Option<InventoryItem> {
-        let npc_inventory = self.current_room_mut().npc_mut().inventory_mut();
+    // Move `count` of the item called `item_name` (or all of them if
+    // `count` is None) from one container to another, and report the
+    // name and number of what moved. Every command that moves items
+    // around goes through here. (Errors are boxed because some Events
+    // are large, and most transfers succeed.)
+    fn transfer(&mut self,
+                item_name: &str,
+                count: Option<u64>,
+                from: &Container,
+                to: &Container)
+                -> Result<(String, u64), Box<Event>> {
+        if self.items_mut(to).is_none() {
+            return Err(Box::new(self.no_such_container(to)));
+        }
 
-        npc_inventory.iter()
-            .position(|thing| item_name == thing.name)
-            .map(|i| npc_inventory.remove(i))
+        let item = match self.items_mut(from) {
+            Some(items) => {
+                inventory_item::remove(items, item_name, count)
+                    .map_err(|missing| Box::new(missing_item(missing, item_name, from.clone())))?
+            }
+            None => return Err(Box::new(self.no_such_container(from))),
+        };
+        let moved = (item.name.clone(), item.count());
+
+        // `to` was there a moment ago, so if it's gone now it was the
+        // item we just took out. Put it back where it came from.
+        match self.items_mut(to) {
+            Some(items) => inventory_item::add(items, item),
+            None => {
+                let event = Event::ItemInItself { item: item.name.clone() };
+                inventory_item::add(self.items_mut(from).expect("BUG: we just took it from here"),
+                                    item);
+                return Err(Box::new(event));
+            }
+        }
+
+        Ok(moved)
+    }
+
+    // Why items can't be moved in or out of `container`.
+    fn no_such_container(&self, container: &Container) -> Event {
+        match *container {
+            Container::Npc(ref name) => Event::NpcNotFound { name: name.clone() },
+            Container::Item(ref name) => {
+                match self.find_item(name) {
+                    Some(item) if item.contents.is_some() => {
+                        Event::ContainerClosed { item: item.name.clone() }
+                    }
+                    Some(_) => Event::NotAContainer { name: name.clone() },
+                    None => Event::ItemNotNearby { name: name.clone() },
+                }
+            }
+            Container::Room | Container::Player => {
+                unreachable!("BUG: the room and the player can always hold items")
+            }
+        }
     }
 
-    fn string_to_player_item(&mut self, item_name: &str) -> Option<InventoryItem> {
-        let player_inventory = self.player_mut().inventory_mut();
+    fn pick_up(&mut self, item_name: &str, count: Option<u64>) -> Event {
+        self.pick_up_from(item_name, count, &Container::Room)
+    }
 
-        player_inventory.iter()
-            .position(|thing| item_name == thing.name)
-            .map(|i| player_inventory.remove(i))
+    fn pick_up_from(&mut self, item_name: &str, count: Option<u64>, from: &Container) -> Event {
+        match self.transfer(item_name, count, from, &Container::Player) {
+            Ok((item, count)) => {
+                Event::ItemPickedUp {
+                    item: item,
+                    count: count,
+                }
+            }
+            Err(event) => *event,
+        }
     }
 
-    fn pick_up(&mut self, item_name: &str) {
-        match self.string_to_inventory_item(item_name) {
-            Some(item) => {
-                self.player.add_to_inventory(item);
+    // Take from one of the NPCs in this room, or from a container.
+    // The player doesn't have to say which NPC if only one of them has
+    // the item.
+    fn take(&mut self, item_name: &str, count: Option<u64>, from: Option<&str>) -> Event {
+        let npc = match from {
+            Some(from) => {
+                match self.current_room().npc(from) {
+                    Some(npc) => npc.name.clone(),
+                    None if self.find_item(from).is_some() => {
+                        let container = Container::Item(from.to_string());
+                        return self.pick_up_from(item_name, count, &container);
+                    }
+                    None => return Event::NpcNotFound { name: from.to_string() },
+                }
+            }
+            None => {
+                let npcs = self.current_room().npcs();
+                let holding: Vec<&NPC> = npcs.iter()
+                    .filter(|npc| npc.inventory.iter().any(|item| item.is_called(item_name)))
+                    .cloned()
+                    .collect();
+
+                // if nobody has it, let the player know which NPC
+                // doesn't have it, if there's only one to ask
+                let npc = if holding.is_empty() {
+                    only_npc(&npcs)
+                } else {
+                    only_npc(&holding)
+                };
+                match npc {
+                    Ok(npc) => npc,
+                    Err(event) => return event,
+                }
             }
-            None => println!("Sorry, {} wasn't found in the current room", item_name),
         };
+
+        // items the NPC trades have to be traded for
+        if let Some(event) = self.trade_wants(&npc, item_name) {
+            return event;
+        }
+
+        match self.transfer(item_name, count, &Container::Npc(npc.clone()), &Container::Player) {
+            Ok((item, count)) => {
+                Event::ItemTaken {
+                    item: item,
+                    count: count,
+                    npc: npc,
+                }
+            }
+            Err(event) => *event,
+        }
     }
 
-    fn take(&mut self, item_name: &str) {
-        match self.string_to_npc_item(item_name) {
-            Some(item) => {
-                self.player.add_to_inventory(item);
+    fn drop(&mut self, item_name: &str, count: Option<u64>) -> Event {
+        match self.transfer(item_name, count, &Container::Player, &Container::Room) {
+            Ok((item, count)) => {
+                Event::ItemDropped {
+                    item: item,
+                    count: count,
+                }
+            }
+            Err(event) => *event,
+        }
+    }
+
+    fn give(&mut self, item_name: &str, count: Option<u64>, to: &str) -> Event {
+        let npc = match self.current_room().npc(to) {
+            Some(npc) => npc.name.clone(),
+            None => return Event::NpcNotFound { name: to.to_string() },
+        };
+
+        match self.transfer(item_name, count, &Container::Player, &Container::Npc(npc.clone())) {
+            Ok((item, count)) => {
+                Event::ItemGiven {
+                    item: item,
+                    count: count,
+                    npc: npc,
+                }
+            }
+            Err(event) => *event,
+        }
+    }
+
+    // TRADING //
+
+    // Swap `offer` (or the world's currency, if it's None) for one of
+    // the items called `item_name` that one of the NPCs here has, if
+    // they have a trade for it and the player can pay.
+    fn trade(&mut self,
+             offer: Option<&str>,
+             count: Option<u64>,
+             item_name: &str,
+             with: Option<&str>)
+             -> Event {
+        let npc = {
+            let room = self.current_room();
+            let npc = match with {
+                Some(with) => room.npc(with).ok_or_else(|| with.to_string()),
+                None => {
+                    let traders: Vec<&NPC> = room.npcs()
+                        .into_iter()
+                        .filter(|npc| npc.trade_for(item_name).is_some())
+                        .collect();
+                    match only_npc(&traders) {
+                        Ok(name) => room.npc(&name).ok_or(name),
+                        Err(event) => return event,
+                    }
+                }
+            };
+            match npc {
+                Ok(npc) => npc.clone(),
+                Err(name) => return Event::NpcNotFound { name: name },
+            }
+        };
+
+        let trade = match npc.trade_for(item_name) {
+            Some(trade) if trade.conditions.iter().all(|c| c.holds(&self.player)) => trade,
+            _ => {
+                return Event::TradeRefused {
+                    npc: npc.name.clone(),
+                    item: item_name.to_string(),
+                }
             }
+        };
+        let wants = match trade.wants.as_ref().or(self.map.currency.as_ref()) {
+            Some(wants) => wants.clone(),
             None => {
-                println!("Sorry, {} doesn't have {}.",
-                         self.current_room().npc().name,
-                         item_name)
+                return Event::TradeRefused {
+                    npc: npc.name.clone(),
+                    item: item_name.to_string(),
+                }
             }
+        };
+
+        let offered_enough = count.map(|count| count >= trade.price).unwrap_or(true);
+        if !offer.map(|offer| called(&wants, offer)).unwrap_or(true) || !offered_enough {
+            return Event::TradeWants {
+                npc: npc.name.clone(),
+                item: trade.gives.clone(),
+                wants: wants,
+                price: trade.price,
+            };
+        }
+
+        if !npc.inventory.iter().any(|item| item.is_called(&trade.gives)) {
+            return Event::ItemNotFound {
+                name: item_name.to_string(),
+                container: Container::Npc(npc.name.clone()),
+            };
+        }
+
+        let from = Container::Npc(npc.name.clone());
+        let (gave, gave_count) =
+            match self.transfer(&wants, Some(trade.price), &Container::Player, &from) {
+                Ok(moved) => moved,
+                Err(event) => return *event,
+            };
+        let (got, _) = self.transfer(&trade.gives, Some(1), &from, &Container::Player)
+            .expect("BUG: we just checked the NPC has it");
+
+        Event::Traded {
+            npc: npc.name.clone(),
+            gave: gave,
+            gave_count: gave_count,
+            got: got,
         }
     }
 
-    fn use_item(&mut self, item_name: &str) {
-        match self.string_to_player_item(item_name) {
-            Some(item) => println!("{}", item.effects),
-            None => println!("Sorry, you don't have {} in your inventory.", item_name),
+    // What the NPC called `npc` wants for `item_name`, if they trade
+    // it. This says the same as `trade` would if the NPC won't trade
+    // it right now, or doesn't have it any more.
+    fn trade_wants(&self, npc: &str, item_name: &str) -> Option<Event> {
+        let npc = self.current_room().npc(npc)?;
+        let trade = npc.trade_for(item_name)?;
+        let wants = trade.wants.as_ref().or(self.map.currency.as_ref());
+
+        let event = match wants {
+            Some(wants) if trade.conditions.iter().all(|c| c.holds(&self.player)) => {
+                if !npc.inventory.iter().any(|item| item.is_called(&trade.gives)) {
+                    Event::ItemNotFound {
+                        name: item_name.to_string(),
+                        container: Container::Npc(npc.name.clone()),
+                    }
+                } else {
+                    Event::TradeWants {
+                        npc: npc.name.clone(),
+                        item: trade.gives.clone(),
+                        wants: wants.clone(),
+                        price: trade.price,
+                    }
+                }
+            }
+            _ => {
+                Event::TradeRefused {
+                    npc: npc.name.clone(),
+                    item: item_name.to_string(),
+                }
+            }
+        };
+        Some(event)
+    }
+
+    fn put(&mut self, item_name: &str, count: Option<u64>, into: &str) -> Event {
+        let container = Container::Item(into.to_string());
+
+        match self.transfer(item_name, count, &Container::Player, &container) {
+            Ok((item, count)) => {
+                Event::ItemPut {
+                    item: item,
+                    count: count,
+                    container: into.to_string(),
+                }
+            }
+            Err(event) => *event,
         }
     }
 
-    fn change_location(&mut self, direction: &str) {
-        let valid_directions = self.map.valid_directions(&self.player().location());
+    // Using an item uses up one of it, tells the player about it (with
+    // the item's `effects` text) and then carries out its actions.
+    fn use_item(&mut self, item_name: &str) -> Vec<Event> {
+        let item = match inventory_item::remove(self.player.inventory_mut(), item_name, Some(1)) {
+            Ok(item) => item,
+            Err(missing) => return vec![missing_item(missing, item_name, Container::Player)],
+        };
+
+        let mut events = vec![Event::ItemUsed {
+                                  item: item.name.clone(),
+                                  effects: item.effects.clone(),
+                              }];
+
+        for action in &item.actions {
+            events.push(effect::apply(action, &mut self.player, &mut self.map, &mut self.rng));
+        }
+
+        events
+    }
+
+    // Move, getting away from anyone who was fighting the player.
+    fn go(&mut self, dir: Direction) -> Vec<Event> {
+        let hostile: Vec<String> = self.current_room()
+            .npcs()
+            .into_iter()
+            .filter(|npc| npc.is_hostile())
+            .map(|npc| npc.name.clone())
+            .collect();
+
+        let moved = self.change_location(dir);
+        if hostile.is_empty() || !matches!(moved, Event::Moved { .. }) {
+            vec![moved]
+        } else {
+            vec![moved, Event::Fled { npcs: hostile }]
+        }
+    }
+
+    fn change_location(&mut self, dir: Direction) -> Event {
+        let from = self.player().location().clone();
+
+        let to = match self.map.exit(&from, dir) {
+            Some(exit) if exit.is_locked() => return Event::ExitLocked { dir: dir },
+            Some(exit) => exit.to.clone(),
+            None => return Event::BlockedDirection { dir: dir },
+        };
+
+        *self.player_mut().location_mut() = to.clone();
+
+        Event::Moved {
+            dir: dir,
+            from: from,
+            to: to,
+        }
+    }
+
+    fn unlock(&mut self, what: Lockable, item_name: &str) -> Event {
+        let here = self.player().location().clone();
+
+        let lock = match what {
+            Lockable::Exit(dir) => {
+                match self.map.exit(&here, dir) {
+                    Some(exit) => exit.lock.clone(),
+                    None => return Event::BlockedDirection { dir: dir },
+                }
+            }
+            Lockable::Item(ref name) => {
+                match self.find_item(name) {
+                    Some(item) => item.lock.clone(),
+                    None => return Event::ItemNotNearby { name: name.clone() },
+                }
+            }
+        };
 
-        if direction == "north" {
-            if valid_directions.north {
-                self.player_mut().location_mut().y += 1;
-                println!("You have moved north.");
-            } else {
-                println!("You can not go north. Try a different direction.");
+        let (key, consume) = match lock {
+            Some(ref lock) if lock.locked => (lock.key.clone(), lock.consume),
+            _ => return Event::NotLocked { what: what },
+        };
+
+        let item = self.player()
+            .inventory()
+            .iter()
+            .find(|item| item.is_called(item_name))
+            .map(|item| item.name.clone());
+
+        let item = match item {
+            Some(item) => item,
+            None => {
+                return Event::ItemNotFound {
+                    name: item_name.to_string(),
+                    container: Container::Player,
+                }
             }
-        } else if direction == "south" {
-            if valid_directions.south {
-                self.player_mut().location_mut().y -= 1;
-                println!("You have moved south.");
-            } else {
-                println!("You can not go south. Try a different direction.");
+        };
+
+        if item != key {
+            return Event::WrongKey {
+                what: what,
+                item: item,
+            };
+        }
+
+        if consume {
+            let _ = inventory_item::remove(self.player.inventory_mut(), &item, Some(1));
+        }
+
+        match what {
+            Lockable::Exit(dir) => {
+                self.map.unlock(&here, dir);
             }
-        } else if direction == "west" {
-            if valid_directions.west {
-                println!("You have moved west.");
-                self.player_mut().location_mut().x -= 1;
-            } else {
-                println!("You can not go west. Try a different direction.");
+            Lockable::Item(ref name) => {
+                if let Some(lock) = self.find_item_mut(name).and_then(|item| item.lock.as_mut()) {
+                    lock.locked = false;
+                }
             }
-        } else if direction == "east" {
-            if valid_directions.east {
-                println!("You have moved east.");
-                self.player_mut().location_mut().x += 1;
-            } else {
-                println!("You can not go north. Try a different direction.");
+        }
+
+        Event::Unlocked {
+            what: what,
+            item: item,
+            consumed: consume,
+        }
+    }
+
+    // CONTAINERS //
+
+    fn open(&mut self, name: &str) -> Event {
+        let item = match self.find_item_mut(name) {
+            Some(item) => item,
+            None => return Event::ItemNotNearby { name: name.to_string() },
+        };
+
+        if item.contents.is_none() {
+            Event::NotAContainer { name: name.to_string() }
+        } else if !item.closed {
+            Event::AlreadyOpen { item: item.name.clone() }
+        } else if item.is_locked() {
+            Event::ContainerLocked { item: item.name.clone() }
+        } else {
+            item.closed = false;
+            Event::Opened {
+                item: item.name.clone(),
+                contents: item.contents.clone().unwrap_or_default(),
             }
+        }
+    }
+
+    fn close(&mut self, name: &str) -> Event {
+        let item = match self.find_item_mut(name) {
+            Some(item) => item,
+            None => return Event::ItemNotNearby { name: name.to_string() },
+        };
+
+        if item.contents.is_none() {
+            Event::NotAContainer { name: name.to_string() }
+        } else if item.closed {
+            Event::AlreadyClosed { item: item.name.clone() }
         } else {
-            println!("That is not a valid direction. Try north, south, east, or west.");
+            item.closed = true;
+            Event::Closed { item: item.name.clone() }
         }
     }
 
-    fn look_around(&self) {
-        // display the current room's description
-        println!("{}", &self.current_room().description);
-        // if the room has any items, display information about them
-        if !&self.current_room().items.is_empty() {
-            println!("This room contains: {:?}", &self.current_room().items);
+    fn look_in(&self, name: &str) -> Event {
+        match self.find_item(name) {
+            Some(item) => {
+                match item.open_contents() {
+                    Some(contents) => {
+                        Event::LookedIn {
+                            item: item.name.clone(),
+                            contents: contents.clone(),
+                        }
+                    }
+                    None => self.no_such_container(&Container::Item(name.to_string())),
+                }
+            }
+            None => Event::ItemNotNearby { name: name.to_string() },
+        }
+    }
+
+    fn look_around(&self) -> Event {
+        let room = self.current_room();
+
+        Event::LookedAround {
+            description: room.description.clone(),
+            exits: self.map.valid_directions(&room.location),
+            locked: self.map
+                .valid_directions(&room.location)
+                .into_iter()
+                .filter(|&dir| room.exits[&dir].is_locked())
+                .collect(),
+            items: room.items.clone(),
+            npcs: room.npcs().into_iter().cloned().collect(),
         }
-        // display information about the room's NPC
-        println!("{} is here too!", &self.current_room().npc.name);
+    }
+
+    fn talk(&mut self, to: Option<&str>) -> Vec<Event> {
+        let (name, tree) = {
+            let room = self.current_room();
+
+            let npc = match to {
+                Some(to) => {
+                    match room.npc(to) {
+                        Some(npc) => npc,
+                        None => return vec![Event::NpcNotFound { name: to.to_string() }],
+                    }
+                }
+                None => {
+                    match only_npc(&room.npcs()) {
+                        Ok(name) => room.npc(&name).expect("BUG: only_npc picks an NPC here"),
+                        Err(event) => return vec![event],
+                    }
+                }
+            };
+
+            match npc.tree {
+                Some(ref tree) => (npc.name.clone(), tree.start.clone()),
+                None => {
+                    return vec![Event::Talked {
+                                    npc: npc.name.clone(),
+                                    dialogue: npc.dialogue.clone(),
+                                }]
+                }
+            }
+        };
 
-        if !&self.current_room().npc().inventory.is_empty() {
-            println!("{} has {:?}.",
-                     &self.current_room().npc.name,
-                     &self.current_room().npc.inventory)
+        self.say(Talking {
+            npc: name,
+            node: tree,
+        })
+    }
+
+    // Have the NPC say the line at `talking.node`, and carry on the
+    // conversation if the player has anything to say back.
+    fn say(&mut self, talking: Talking) -> Vec<Event> {
+        let (line, responses): (String, Vec<String>) = match self.dialogue_node(&talking) {
+            Some(node) => {
+                (node.line.clone(),
+                 node.responses_for(&self.player).iter().map(|r| r.text.clone()).collect())
+            }
+            // the world file points at a node that isn't there
+            // (check-world reports these), so just stop talking
+            None => return vec![Event::ConversationEnded { npc: talking.npc }],
+        };
+
+        let npc = talking.npc.clone();
+        if !responses.is_empty() {
+            self.talking = Some(talking);
         }
+
+        vec![Event::Said {
+                 npc: npc,
+                 line: line,
+                 responses: responses,
+             }]
     }
 
-    fn talk(&self) {
-        println!("{}", &self.current_room().npc.dialogue);
+    // The player picked a response (hopefully) while talking.
+    fn respond(&mut self, talking: Talking, user_input: &str) -> Vec<Event> {
+        let user_input = user_input.trim().to_lowercase();
+
+        if user_input == "bye" || user_input == "leave" {
+            return vec![Event::ConversationEnded { npc: talking.npc }];
+        }
+
+        let response = match self.dialogue_node(&talking) {
+            Some(node) => {
+                let responses = node.responses_for(&self.player);
+                match user_input.parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= responses.len() => responses[n - 1].clone(),
+                    _ => {
+                        let event = Event::NotAResponse { responses: responses.len() };
+                        self.talking = Some(talking);
+                        return vec![event];
+                    }
+                }
+            }
+            None => return vec![Event::ConversationEnded { npc: talking.npc }],
+        };
+
+        let mut events: Vec<Event> = response.effects
+            .iter()
+            .map(|effect| effect::apply(effect, &mut self.player, &mut self.map, &mut self.rng))
+            .collect();
+
+        match response.goto {
+            Some(node) => {
+                events.extend(self.say(Talking {
+                    npc: talking.npc,
+                    node: node,
+                }))
+            }
+            None => events.push(Event::ConversationEnded { npc: talking.npc }),
+        }
+
+        events
+    }
+
+    fn dialogue_node(&self, talking: &Talking) -> Option<&Node> {
+        self.current_room()
+            .npc(&talking.npc)
+            .and_then(|npc| npc.tree.as_ref())
+            .and_then(|tree| tree.nodes.get(&talking.node))
+    }
+
+    // FIGHTING //
+
+    fn attack(&mut self, to: Option<&str>, with: Option<&str>) -> Vec<Event> {
+        let npc = {
+            let npcs = self.current_room().npcs();
+            match to {
+                Some(to) => {
+                    match npcs.iter().find(|npc| npc.is_called(to)) {
+                        Some(npc) => Ok(npc.name.clone()),
+                        None => Err(Event::NpcNotFound { name: to.to_string() }),
+                    }
+                }
+                None => {
+                    // pick on whoever is already fighting, if anyone is
+                    let hostile: Vec<&NPC> =
+                        npcs.iter().filter(|npc| npc.is_hostile()).cloned().collect();
+                    if hostile.is_empty() {
+                        only_npc(&npcs)
+                    } else {
+                        only_npc(&hostile)
+                    }
+                }
+            }
+        };
+        let npc = match npc {
+            Ok(npc) => npc,
+            Err(event) => return vec![event],
+        };
+
+        let (weapon, most) = match with {
+            Some(name) => {
+                match self.player.inventory().iter().find(|item| item.is_called(name)) {
+                    Some(item) => {
+                        match item.damage {
+                            Some(damage) => (Some(item.name.clone()), damage),
+                            None => return vec![Event::NotAWeapon { item: item.name.clone() }],
+                        }
+                    }
+                    None => return vec![missing_item(Missing::NotFound, name, Container::Player)],
+                }
+            }
+            None => (None, FIST_DAMAGE),
+        };
+
+        let damage = if most > 0 { 1 + self.rng.below(most) } else { 0 };
+        let health = {
+            let npc = self.current_room_mut().npc_mut(&npc).expect("BUG: the NPC was found above");
+            match npc.fight {
+                Some(ref mut fight) => {
+                    fight.hostile = true;
+                    fight.health = fight.health.saturating_sub(damage);
+                    fight.health
+                }
+                None => return vec![Event::WontFight { npc: npc.name.clone() }],
+            }
+        };
+
+        let mut events = vec![Event::Attacked {
+                                  npc: npc.clone(),
+                                  with: weapon,
+                                  damage: damage,
+                                  health: health,
+                              }];
+
+        if health == 0 {
+            // whatever they had falls on the floor
+            let room = self.current_room_mut();
+            let i = room.npcs.iter().position(|n| n.name == npc).expect("BUG: the NPC is here");
+            let dead = room.npcs.remove(i);
+            for item in dead.inventory.iter().cloned() {
+                inventory_item::add(&mut room.items, item);
+            }
+
+            events.push(Event::NpcKilled {
+                npc: npc,
+                dropped: dead.inventory,
+            });
+        }
+
+        events
+    }
+
+    fn display_map(&self) -> Event {
+        // the whole map, if the player has seen one
+        let visited = Some(&self.player.visited).filter(|_| !self.player.map_revealed);
+
+        Event::MapShown { map: self.map.draw(Some(&self.player.location), visited) }
+    }
+
+    // SAVING //
+
+    fn save(&self, slot: &str) -> Event {
+        let result = save::slot_path(&self.save_dir, slot)
+            .and_then(|path| {
+                let save = SaveFile::new(self.player.clone(),
+                                         self.map.clone(),
+                                         self.turn,
+                                         self.rng.clone());
+                save::save(&path, &save)
+            });
+
+        match result {
+            Ok(()) => Event::Saved { slot: slot.to_string() },
+            Err(err) => {
+                Event::SaveFailed {
+                    slot: slot.to_string(),
+                    reason: err.to_string(),
+                }
+            }
+        }
+    }
+
+    // Start the game again from the beginning.
+    fn restart(&mut self) -> Event {
+        let (player, map) = self.beginning.clone();
+        self.player = player;
+        self.map = map;
+        self.turn = 0;
+        self.rng = Rng::new(self.rng.seed());
+        self.game_over = false;
+        self.forget();
+        self.follow_schedules();
+        Event::Restarted
+    }
+
+    fn load(&mut self, slot: &str) -> Event {
+        let result = save::slot_path(&self.save_dir, slot).and_then(|path| save::load(&path));
+
+        match result {
+            Ok(save) => {
+                self.player = save.player;
+                self.map = save.map;
+                self.turn = save.turn;
+                self.rng = save.rng;
+                self.game_over = false;
+                self.forget();
+                Event::Loaded { slot: slot.to_string() }
+            }
+            Err(err) => {
+                Event::LoadFailed {
+                    slot: slot.to_string(),
+                    reason: err.to_string(),
+                }
+            }
+        }
+    }
+}
+
+// The event for when `name` couldn't be taken out of `container`.
+fn missing_item(missing: Missing, name: &str, container: Container) -> Event {
+    match missing {
+        Missing::NotFound => {
+            Event::ItemNotFound {
+                name: name.to_string(),
+                container: container,
+            }
+        }
+        Missing::NotEnough { have } => {
+            Event::NotEnough {
+                name: name.to_string(),
+                container: container,
+                have: have,
+            }
+        }
+    }
+}
+
+// The name of the one NPC in `npcs`, for when the player doesn't say
+// who they mean. If there's no one, or more than one, there's nobody
+// to pick, and the event says why.
+fn only_npc(npcs: &[&NPC]) -> Result<String, Event> {
+    let names: Vec<String> = npcs.iter().map(|npc| npc.name.clone()).collect();
+
+    match names.len() {
+        0 => Err(Event::NoOneHere),
+        1 => Ok(names[0].clone()),
+        _ => Err(Event::WhichNpc { npcs: names }),
     }
 }
 
-fn regex(s: &str) -> regex::Regex {
-    regex::Regex::new(s).unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use effect::Effect;
+    use exit::Lock;
+    use location::Location;
+    use dialogue::Condition;
+    use npc::{Fighter, NPC, Schedule, Trade};
+    use quest::{Objective, Win};
+    use room::Hazard;
+    use serde_json;
+
+    // A 2x1 map with a potion on the floor of the west room, and an
+    // NPC holding a hat in the east room.
+    fn map() -> Map {
+        let potion = InventoryItem::new(1, "potion".to_string(), "you feel great".to_string());
+        let hat = InventoryItem::new(1, "hat".to_string(), "you look great".to_string());
+
+        Map::new("Test Adventure",
+                 vec![Room::new(0,
+                                0,
+                                "west".to_string(),
+                                "the west room".to_string(),
+                                vec![potion],
+                                vec![NPC::new("Wes".to_string(),
+                                              vec![],
+                                              "hi I'm Wes".to_string())]),
+                      Room::new(1,
+                                0,
+                                "east".to_string(),
+                                "the east room".to_string(),
+                                vec![],
+                                vec![NPC::new("Eve".to_string(),
+                                              vec![hat],
+                                              "hi I'm Eve".to_string())])])
+    }
+
+    // Run `script` through a fresh game and return the game along
+    // with everything it printed.
+    fn play(script: &str) -> (Game<&[u8], Vec<u8>>, String) {
+        let player = Player::new("Liz".to_string(), vec![], 0, 0);
+        let mut game = Game::new(player, map(), true, script.as_bytes(), vec![]);
+        game.run().unwrap();
+
+        let output = String::from_utf8(game.output().clone()).unwrap();
+        (game, output)
+    }
+
+    #[test]
+    fn stops_when_the_input_runs_out() {
+        let (game, output) = play("");
+
+        assert!(!game.playing);
+        assert!(output.starts_with("Hi Liz\nWelcome to Test Adventure\n"));
+    }
+
+    #[test]
+    fn picks_up_items_from_the_room() {
+        let (game, output) = play("pick up potion\n");
+
+        assert!(output.contains("potion has been added to your inventory!"));
+        assert_eq!(game.player.inventory().len(), 1);
+        assert!(game.current_room().items.is_empty());
+    }
+
+    fn game() -> Game<&'static [u8], Vec<u8>> {
+        let player = Player::new("Liz".to_string(), vec![], 0, 0);
+        Game::new(player, map(), true, &b""[..], vec![])
+    }
+
+    #[test]
+    fn commands_return_events() {
+        let mut game = game();
+
+        assert_eq!(game.execute("move west"),
+                   vec![Event::BlockedDirection { dir: Direction::West }]);
+        assert_eq!(game.execute("move east"),
+                   vec![Event::Moved {
+                            dir: Direction::East,
+                            from: Location::new(0, 0),
+                            to: Location::new(1, 0),
+                        }]);
+        assert_eq!(game.execute("take sword"),
+                   vec![Event::ItemNotFound {
+                            name: "sword".to_string(),
+                            container: Container::Npc("Eve".to_string()),
+                        }]);
+        assert_eq!(game.execute("take hat from mary"),
+                   vec![Event::NpcNotFound { name: "mary".to_string() }]);
+        assert_eq!(game.execute("take Hat from eve"),
+                   vec![Event::ItemTaken {
+                            item: "hat".to_string(),
+                            count: 1,
+                            npc: "Eve".to_string(),
+                        }]);
+    }
+
+    #[test]
+    fn moves_and_takes_from_npcs() {
+        let (game, output) = play("move east\ntake hat\nmove east\n");
+
+        assert!(output.contains("You have moved east."));
+        assert!(output.contains("hat has been added to your inventory!"));
+        assert!(output.contains("You can not go east."));
+        assert_eq!(game.player.location, Location::new(1, 0));
+    }
+
+    #[test]
+    fn locked_exits_need_the_right_key() {
+        let mut game = game();
+        game.map.exit_mut(&Location::new(0, 0), Direction::East).unwrap().lock = Some(Lock {
+            key: "brass key".to_string(),
+            consume: true,
+            locked: true,
+        });
+        game.map.exit_mut(&Location::new(1, 0), Direction::West).unwrap().lock = Some(Lock {
+            key: "brass key".to_string(),
+            consume: true,
+            locked: true,
+        });
+        let key = InventoryItem::new(1, "brass key".to_string(), String::new());
+        game.player.add_to_inventory(key);
+
+        assert_eq!(game.execute("e"), vec![Event::ExitLocked { dir: Direction::East }]);
+        assert_eq!(game.execute("unlock east with potion"),
+                   vec![Event::ItemNotFound {
+                            name: "potion".to_string(),
+                            container: Container::Player,
+                        }]);
+        assert_eq!(game.execute("unlock east with brass key"),
+                   vec![Event::Unlocked {
+                            what: Lockable::Exit(Direction::East),
+                            item: "brass key".to_string(),
+                            consumed: true,
+                        }]);
+        assert!(game.player.inventory().is_empty());
+        assert_eq!(game.execute("e")[0], Event::Moved {
+            dir: Direction::East,
+            from: Location::new(0, 0),
+            to: Location::new(1, 0),
+        });
+        // the other side of the door was unlocked too
+        assert!(!game.map.exit(&Location::new(1, 0), Direction::West).unwrap().is_locked());
+    }
+
+    #[test]
+    fn using_items_applies_their_effects() {
+        let mut game = game();
+        let mut potion = InventoryItem::new(1, "cool potion".to_string(), "so cool".to_string());
+        potion.actions = vec![Effect::SetFlag("C00L d00d".to_string()),
+                              Effect::Heal(5),
+                              Effect::Teleport(Location::new(1, 0))];
+        game.player.health = 50;
+        game.player.add_to_inventory(potion);
+
+        assert_eq!(game.execute("use cool potion"),
+                   vec![Event::ItemUsed {
+                            item: "cool potion".to_string(),
+                            effects: "so cool".to_string(),
+                        },
+                        Event::FlagSet { flag: "C00L d00d".to_string() },
+                        Event::Healed {
+                            amount: 5,
+                            health: 55,
+                        },
+                        Event::Teleported { to: Location::new(1, 0) }]);
+        assert!(game.player.has_flag("C00L d00d"));
+        assert_eq!(game.player.location, Location::new(1, 0));
+
+        // however much an item heals, it's never more than max health
+        let mut elixir = InventoryItem::new(1, "elixir".to_string(), String::new());
+        elixir.actions = vec![Effect::Heal(u64::MAX)];
+        game.player.add_to_inventory(elixir);
+        game.execute("use elixir");
+        assert_eq!(game.player.health, game.player.max_health);
+    }
+
+    #[test]
+    fn items_stack_and_are_used_one_at_a_time() {
+        let mut game = game();
+        let potions = InventoryItem::new(2, "potion".to_string(), "you feel great".to_string());
+        inventory_item::add(game.current_room_mut().items_mut(), potions);
+
+        // the room's two potions join the one already on the floor
+        assert_eq!(game.current_room().items[0].count(), 3);
+        assert_eq!(game.execute("pick up 2 potions"),
+                   vec![Event::ItemPickedUp {
+                            item: "potion".to_string(),
+                            count: 2,
+                        }]);
+        assert_eq!(game.execute("pick up 2 potions"),
+                   vec![Event::NotEnough {
+                            name: "potions".to_string(),
+                            container: Container::Room,
+                            have: 1,
+                        }]);
+        game.execute("pick up potion");
+        assert_eq!(game.player.inventory().len(), 1);
+        assert_eq!(game.player.inventory()[0].count(), 3);
+
+        game.execute("use potion");
+        assert_eq!(game.player.inventory()[0].count(), 2);
+        assert_eq!(game.execute("drop 1 potion"),
+                   vec![Event::ItemDropped {
+                            item: "potion".to_string(),
+                            count: 1,
+                        }]);
+        assert_eq!(game.player.inventory()[0].count(), 1);
+        assert_eq!(game.current_room().items[0].count(), 1);
+    }
+
+    #[test]
+    fn putting_something_in_one_bag_doesnt_copy_it() {
+        let mut game = game();
+        // like a world that says there are two bags
+        let bags = r#"{ "count": 2, "name": "bag", "effects": "", "contents": [] }"#;
+        game.player.add_to_inventory(serde_json::from_str(bags).unwrap());
+        game.execute("pick up potion");
+
+        game.execute("put potion in bag");
+        game.execute("drop 1 bag");
+        let potions = |items: &[InventoryItem]| {
+            items.iter()
+                .flat_map(|item| item.contents.iter().flat_map(|contents| contents.iter()))
+                .filter(|item| item.name == "potion")
+                .count()
+        };
+        assert_eq!(potions(game.player.inventory()) + potions(&game.current_room().items), 1);
+    }
+
+    #[test]
+    fn gives_and_puts_items_away() {
+        let mut game = game();
+        let bag = InventoryItem::container("bag".to_string(), String::new());
+        game.player.add_to_inventory(bag);
+        game.execute("pick up potion");
+
+        assert_eq!(game.execute("give potion to eve"),
+                   vec![Event::NpcNotFound { name: "eve".to_string() }]);
+        assert_eq!(game.execute("put potion in potion"),
+                   vec![Event::NotAContainer { name: "potion".to_string() }]);
+        assert_eq!(game.execute("put bag in bag"),
+                   vec![Event::ItemInItself { item: "bag".to_string() }]);
+        assert_eq!(game.execute("put potion in bag"),
+                   vec![Event::ItemPut {
+                            item: "potion".to_string(),
+                            count: 1,
+                            container: "bag".to_string(),
+                        }]);
+        assert_eq!(game.player.inventory().len(), 1);
+        assert_eq!(game.player.inventory()[0].contents.as_ref().unwrap()[0].name, "potion");
+
+        game.execute("e");
+        assert_eq!(game.execute("give bag to Eve"),
+                   vec![Event::ItemGiven {
+                            item: "bag".to_string(),
+                            count: 1,
+                            npc: "Eve".to_string(),
+                        }]);
+        assert!(game.player.inventory().is_empty());
+        assert_eq!(game.current_room().npcs[0].inventory.len(), 2);
+    }
+
+    #[test]
+    fn containers_open_close_and_lock() {
+        let mut game = game();
+        let mut chest = InventoryItem::container("chest".to_string(), String::new());
+        chest.closed = true;
+        chest.lock = Some(Lock {
+            key: "brass key".to_string(),
+            consume: false,
+            locked: true,
+        });
+        chest.contents = Some(vec![InventoryItem::new(2, "coin".to_string(), String::new())]);
+        game.current_room_mut().items_mut().push(chest);
+        let key = InventoryItem::new(1, "brass key".to_string(), String::new());
+        game.player.add_to_inventory(key);
+
+        assert_eq!(game.execute("look in chest"),
+                   vec![Event::ContainerClosed { item: "chest".to_string() }]);
+        assert_eq!(game.execute("take coin from chest"),
+                   vec![Event::ContainerClosed { item: "chest".to_string() }]);
+        assert_eq!(game.execute("open chest"),
+                   vec![Event::ContainerLocked { item: "chest".to_string() }]);
+        assert_eq!(game.execute("unlock chest with brass key"),
+                   vec![Event::Unlocked {
+                            what: Lockable::Item("chest".to_string()),
+                            item: "brass key".to_string(),
+                            consumed: false,
+                        }]);
+        assert_eq!(game.execute("open chest"),
+                   vec![Event::Opened {
+                            item: "chest".to_string(),
+                            contents: vec![InventoryItem::new(2,
+                                                              "coin".to_string(),
+                                                              String::new())],
+                        }]);
+        assert_eq!(game.execute("take 1 coin from chest"),
+                   vec![Event::ItemPickedUp {
+                            item: "coin".to_string(),
+                            count: 1,
+                        }]);
+        assert_eq!(render::text(&game.execute("look around")[0]).lines().nth(2),
+                   Some("This room contains: potion, chest (coin)"));
+        game.execute("close chest");
+        assert_eq!(game.execute("put coin in chest"),
+                   vec![Event::ContainerClosed { item: "chest".to_string() }]);
+        assert_eq!(render::all(&game.execute("open wardrobe")),
+                   "Sorry, there's no wardrobe here or in your inventory.");
+    }
+
+    #[test]
+    fn picks_between_npcs() {
+        let mut game = game();
+        let scarf = InventoryItem::new(1, "scarf".to_string(), String::new());
+        game.current_room_mut()
+            .npcs
+            .push(NPC::new("Win".to_string(), vec![scarf], "hi I'm Win".to_string()));
+
+        assert_eq!(game.execute("talk"),
+                   vec![Event::WhichNpc { npcs: vec!["Wes".to_string(), "Win".to_string()] }]);
+        assert_eq!(game.execute("talk to win"),
+                   vec![Event::Talked {
+                            npc: "Win".to_string(),
+                            dialogue: "hi I'm Win".to_string(),
+                        }]);
+        // only Win has a scarf, so there's no need to say who from
+        assert_eq!(game.execute("take scarf"),
+                   vec![Event::ItemTaken {
+                            item: "scarf".to_string(),
+                            count: 1,
+                            npc: "Win".to_string(),
+                        }]);
+        assert_eq!(game.execute("give scarf to wes")[0],
+                   Event::ItemGiven {
+                       item: "scarf".to_string(),
+                       count: 1,
+                       npc: "Wes".to_string(),
+                   });
+
+        game.current_room_mut().npcs.clear();
+        assert_eq!(game.execute("talk"), vec![Event::NoOneHere]);
+        let look = render::text(&game.execute("look")[0]);
+        assert!(!look.contains("is here too"));
+    }
+
+    #[test]
+    fn talks_through_dialogue_trees() {
+        let mut game = game();
+        game.current_room_mut().npcs[0].tree = Some(serde_json::from_str(r#"{
+            "start": "hi",
+            "nodes": {
+                "hi": {
+                    "line": "Want a hat?",
+                    "responses": [
+                        { "text": "Yes!", "goto": "hat", "conditions": [{ "lacks_item": "hat" }],
+                          "effects": [{ "give_item": { "name": "hat", "effects": "" } }] },
+                        { "text": "Bye." }
+                    ]
+                },
+                "hat": {
+                    "line": "Here you go.",
+                    "responses": [{ "text": "Thanks!", "goto": "hi" }]
+                }
+            }
+        }"#)
+            .unwrap());
+
+        assert_eq!(game.execute("talk to wes"),
+                   vec![Event::Said {
+                            npc: "Wes".to_string(),
+                            line: "Want a hat?".to_string(),
+                            responses: vec!["Yes!".to_string(), "Bye.".to_string()],
+                        }]);
+        assert_eq!(game.execute("look"), vec![Event::NotAResponse { responses: 2 }]);
+        assert_eq!(game.execute("1"),
+                   vec![Event::ItemReceived {
+                            item: "hat".to_string(),
+                            count: 1,
+                        },
+                        Event::Said {
+                            npc: "Wes".to_string(),
+                            line: "Here you go.".to_string(),
+                            responses: vec!["Thanks!".to_string()],
+                        }]);
+        // now that the player has a hat, they aren't offered another
+        assert_eq!(game.execute("1"),
+                   vec![Event::Said {
+                            npc: "Wes".to_string(),
+                            line: "Want a hat?".to_string(),
+                            responses: vec!["Bye.".to_string()],
+                        }]);
+        assert_eq!(game.execute("bye"),
+                   vec![Event::ConversationEnded { npc: "Wes".to_string() }]);
+        assert_eq!(game.execute("e")[0], Event::Moved {
+            dir: Direction::East,
+            from: Location::new(0, 0),
+            to: Location::new(1, 0),
+        });
+    }
+
+    #[test]
+    fn npcs_trade_by_their_rules() {
+        let mut game = game();
+        game.map.currency = Some("coin".to_string());
+        game.player.add_to_inventory(InventoryItem::new(3, "coin".to_string(), String::new()));
+        game.execute("e");
+        game.current_room_mut().npcs[0].trades = vec![Trade {
+                                                          gives: "hat".to_string(),
+                                                          wants: None,
+                                                          price: 2,
+                                                          conditions: vec![],
+                                                      }];
+        let wants = Event::TradeWants {
+            npc: "Eve".to_string(),
+            item: "hat".to_string(),
+            wants: "coin".to_string(),
+            price: 2,
+        };
+
+        assert_eq!(game.execute("take hat"), vec![wants.clone()]);
+        assert_eq!(game.execute("trade 1 coin for hat"), vec![wants.clone()]);
+        assert_eq!(game.execute("trade potion for hat with eve"), vec![wants]);
+        assert_eq!(game.execute("buy hat from eve"),
+                   vec![Event::Traded {
+                            npc: "Eve".to_string(),
+                            gave: "coin".to_string(),
+                            gave_count: 2,
+                            got: "hat".to_string(),
+                        }]);
+        assert_eq!(game.player.inventory()[0].count(), 1);
+        assert_eq!(game.player.inventory()[1].name, "hat");
+        let gone = Event::ItemNotFound {
+            name: "hat".to_string(),
+            container: Container::Npc("Eve".to_string()),
+        };
+        assert_eq!(game.execute("buy hat"), vec![gone.clone()]);
+        assert_eq!(game.execute("take hat"), vec![gone]);
+
+        // taking says the same as buying when Eve won't trade
+        game.current_room_mut().npcs[0].trades[0].conditions =
+            vec![Condition::HasFlag("friend".to_string())];
+        let refused = Event::TradeRefused {
+            npc: "Eve".to_string(),
+            item: "hat".to_string(),
+        };
+        assert_eq!(game.execute("buy hat"), vec![refused.clone()]);
+        assert_eq!(game.execute("take hat"), vec![refused]);
+    }
+
+    #[test]
+    fn npcs_move_on_the_clock() {
+        let mut game = game();
+        game.current_room_mut().npcs[0].behaviour = Behaviour::Follow;
+
+        assert_eq!(game.execute("look").len(), 1);
+        assert_eq!(game.turn, 0);
+        assert_eq!(game.execute("e"),
+                   vec![Event::Moved {
+                            dir: Direction::East,
+                            from: Location::new(0, 0),
+                            to: Location::new(1, 0),
+                        },
+                        Event::NpcArrived {
+                            npc: "Wes".to_string(),
+                            dir: Some(Direction::West),
+                        }]);
+        assert_eq!(game.turn, 1);
+
+        // Eve walks back and forth between the two rooms
+        let route = vec![Location::new(1, 0), Location::new(0, 0)];
+        game.current_room_mut().npcs[0].behaviour = Behaviour::Patrol(route);
+        assert_eq!(game.execute("take sword")[1],
+                   Event::NpcLeft {
+                       npc: "Eve".to_string(),
+                       dir: Some(Direction::West),
+                   });
+        assert_eq!(game.execute("take sword")[1],
+                   Event::NpcArrived {
+                       npc: "Eve".to_string(),
+                       dir: Some(Direction::West),
+                   });
+    }
+
+    #[test]
+    fn npcs_keep_to_their_schedules() {
+        let mut game = game();
+        game.current_room_mut().npcs[0].schedule = Some(Schedule {
+            from: 2,
+            until: Some(3),
+        });
+        game.follow_schedules();
+
+        assert_eq!(game.execute("talk"), vec![Event::NoOneHere]);
+        assert_eq!(game.execute("take sword")[1],
+                   Event::NpcArrived {
+                       npc: "Wes".to_string(),
+                       dir: None,
+                   });
+        assert_eq!(game.execute("talk")[1],
+                   Event::NpcLeft {
+                       npc: "Wes".to_string(),
+                       dir: None,
+                   });
+    }
+
+    #[test]
+    fn wandering_is_the_same_for_the_same_seed() {
+        let wander = |seed| {
+            let mut game = game();
+            game.rng = Rng::new(seed);
+            game.current_room_mut().npcs[0].behaviour = Behaviour::Wander;
+            (0..10).flat_map(|_| game.execute("take sword")).collect::<Vec<Event>>()
+        };
+
+        assert_eq!(wander(7), wander(7));
+        assert!(wander(7).iter().any(|event| matches!(*event, Event::NpcLeft { .. })));
+    }
+
+    #[test]
+    fn quests_are_tracked_until_the_game_is_won() {
+        let mut game = game();
+        game.map.quests = vec![serde_json::from_str(r#"{
+            "name": "hat trick",
+            "objectives": [{ "visit": "east" }, { "collect": { "item": "hat" } }]
+        }"#)
+                                   .unwrap()];
+        game.map.win = Some(Win::AllQuests);
+
+        assert_eq!(game.execute("e")[1],
+                   Event::ObjectiveDone {
+                       quest: "hat trick".to_string(),
+                       objective: Objective::Visit("east".to_string()),
+                   });
+        assert_eq!(game.execute("e").len(), 1);
+        assert!(game.playing);
+
+        assert_eq!(game.execute("take hat")[1..].to_vec(),
+                   vec![Event::ObjectiveDone {
+                            quest: "hat trick".to_string(),
+                            objective: Objective::Collect {
+                                item: "hat".to_string(),
+                                count: 1,
+                            },
+                        },
+                        Event::QuestDone { quest: "hat trick".to_string() },
+                        Event::Won {
+                            quests: vec!["hat trick".to_string()],
+                            turns: 3,
+                        }]);
+        assert!(!game.playing);
+    }
+
+    #[test]
+    fn hazards_can_kill_the_player() {
+        let mut game = game();
+        game.map.rooms.get_mut(&Location::new(1, 0)).unwrap().hazard = Some(Hazard {
+            description: "It's hot in here.".to_string(),
+            effects: vec![Effect::Damage(60),
+                          Effect::ChangeStat {
+                              stat: "sweatiness".to_string(),
+                              by: 1,
+                          }],
+        });
+
+        assert_eq!(game.execute("e")[1..].to_vec(),
+                   vec![Event::Hazard { description: "It's hot in here.".to_string() },
+                        Event::Hurt {
+                            amount: 60,
+                            health: 40,
+                        },
+                        Event::StatChanged {
+                            stat: "sweatiness".to_string(),
+                            by: 1,
+                            value: 1,
+                        }]);
+        // stats stop at the biggest number there is, instead of overflowing
+        game.player.stats.insert("sweatiness".to_string(), i64::MAX);
+        assert_eq!(game.execute("take hat").last(), Some(&Event::Died));
+        assert_eq!(game.player.stats["sweatiness"], i64::MAX);
+        assert!(game.game_over);
+        assert!(game.playing);
+
+        assert_eq!(game.execute("i"), vec![Event::GameIsOver]);
+        assert_eq!(game.execute("restart"), vec![Event::Restarted]);
+        assert!(!game.game_over);
+        assert_eq!(game.player.health, 100);
+        assert!(game.player.stats.is_empty());
+        assert_eq!(game.map.rooms[&Location::new(1, 0)].npcs[0].inventory.len(), 1);
+    }
+
+    #[test]
+    fn fights_npcs_until_they_drop() {
+        let mut game = game();
+        let mut stick = InventoryItem::new(1, "stick".to_string(), "it's a stick".to_string());
+        stick.damage = Some(1);
+        game.player.add_to_inventory(stick);
+        game.player.add_to_inventory(InventoryItem::new(1, "sock".to_string(), String::new()));
+        game.map.rooms.get_mut(&Location::new(1, 0)).unwrap().npcs[0].fight = Some(Fighter {
+            health: 2,
+            attack: 1,
+            hostile: false,
+        });
+
+        assert_eq!(game.execute("attack wes with sock"),
+                   vec![Event::NotAWeapon { item: "sock".to_string() }]);
+        assert_eq!(game.execute("attack"),
+                   vec![Event::WontFight { npc: "Wes".to_string() }]);
+
+        game.execute("e");
+        assert_eq!(game.execute("attack eve with stick"),
+                   vec![Event::Attacked {
+                            npc: "Eve".to_string(),
+                            with: Some("stick".to_string()),
+                            damage: 1,
+                            health: 1,
+                        },
+                        Event::NpcAttacked {
+                            npc: "Eve".to_string(),
+                            damage: 1,
+                            health: 99,
+                        }]);
+        assert_eq!(game.execute("w")[1],
+                   Event::Fled { npcs: vec!["Eve".to_string()] });
+
+        game.execute("e");
+        assert_eq!(game.execute("hit eve with stick")[1],
+                   Event::NpcKilled {
+                       npc: "Eve".to_string(),
+                       dropped: vec![InventoryItem::new(1,
+                                                        "hat".to_string(),
+                                                        "you look great".to_string())],
+                   });
+        assert!(game.current_room().npcs.is_empty());
+        assert!(game.current_room().items.iter().any(|item| item.is_called("hat")));
+        assert_eq!(game.player.health, 98);
+    }
+
+    #[test]
+    fn random_effects_follow_the_seed() {
+        let coin_toss = |seed| {
+            let mut game = game();
+            game.rng = Rng::new(seed);
+            let mut coin = InventoryItem::new(20, "coin".to_string(), "you flip it".to_string());
+            coin.actions = vec![Effect::OneOf(vec![Effect::SetFlag("heads".to_string()),
+                                                   Effect::SetFlag("tails".to_string())])];
+            game.player.add_to_inventory(coin);
+            (0..20).flat_map(|_| game.execute("use coin")).collect::<Vec<Event>>()
+        };
+
+        assert_eq!(coin_toss(3), coin_toss(3));
+        assert!(coin_toss(3).contains(&Event::FlagSet { flag: "heads".to_string() }));
+        assert!(coin_toss(3).contains(&Event::FlagSet { flag: "tails".to_string() }));
+    }
+
+    #[test]
+    fn the_map_only_shows_where_the_player_has_been() {
+        let grid = |game: &mut Game<&[u8], Vec<u8>>| match game.execute("map").pop() {
+            Some(Event::MapShown { map }) => map,
+            other => panic!("expected the map, got {:?}", other),
+        };
+        let mut game = game();
+
+        // the player can see the way east, but hasn't been there
+        assert!(grid(&mut game).contains("west              ?"));
+        game.execute("e");
+        assert!(grid(&mut game).contains("west            east"));
+        assert!(!grid(&mut game).contains('?'));
+
+        let mut game = self::game();
+        let mut map = InventoryItem::new(1, "map".to_string(), "ooh".to_string());
+        map.actions = vec![Effect::RevealMap];
+        game.player.add_to_inventory(map);
+        assert_eq!(game.execute("use map"),
+                   vec![Event::ItemUsed { item: "map".to_string(), effects: "ooh".to_string() },
+                        Event::MapRevealed]);
+        assert!(grid(&mut game).contains("west            east"));
+        // but the player still hasn't been east
+        assert_eq!(game.player.visited.len(), 1);
+    }
+
+    #[test]
+    fn undoes_and_redoes_commands() {
+        let mut game = game();
+
+        assert_eq!(game.execute("undo"), vec![Event::NothingToUndo]);
+        game.execute("pick up potion");
+        game.execute("look");
+        game.execute("use potion");
+        assert!(game.player.inventory().is_empty());
+
+        // looking didn't change anything, so there's nothing to undo
+        assert_eq!(game.execute("undo"),
+                   vec![Event::Undone { input: "use potion".to_string() }]);
+        assert_eq!(game.player.inventory().len(), 1);
+        assert_eq!(game.turn, 1);
+
+        assert_eq!(game.execute("redo"),
+                   vec![Event::Redone { input: "use potion".to_string() }]);
+        assert!(game.player.inventory().is_empty());
+        assert_eq!(game.execute("redo"), vec![Event::NothingToRedo]);
+
+        // doing something new means there's nothing left to redo
+        game.execute("undo");
+        game.execute("e");
+        assert_eq!(game.execute("redo"), vec![Event::NothingToRedo]);
+
+        for _ in 0..UNDO_LIMIT + 5 {
+            game.execute("take sword");
+        }
+        assert_eq!(game.undo.len(), UNDO_LIMIT);
+
+        game.execute("restart");
+        assert_eq!(game.execute("undo"), vec![Event::NothingToUndo]);
+    }
+
+    #[test]
+    fn hidden_exits_can_be_revealed() {
+        let mut game = game();
+        {
+            let room = game.map.rooms.get_mut(&Location::new(0, 0)).unwrap();
+            room.exits.get_mut(&Direction::East).unwrap().hidden = true;
+        }
+        let mut lever = InventoryItem::new(1, "lever".to_string(), "click".to_string());
+        lever.actions = vec![Effect::RevealExit {
+                                 room: Location::new(0, 0),
+                                 dir: Direction::East,
+                             }];
+        game.player.add_to_inventory(lever);
+
+        assert_eq!(game.execute("e"), vec![Event::BlockedDirection { dir: Direction::East }]);
+        game.execute("use lever");
+        assert_eq!(game.execute("e")[0], Event::Moved {
+            dir: Direction::East,
+            from: Location::new(0, 0),
+            to: Location::new(1, 0),
+        });
+    }
 }
@@ -1,8 +1,39 @@
-#[derive(Debug, Eq, PartialEq)]
+use effect::Effect;
+use exit::Lock;
+
+// A stack of one or more identical things. Two potions with the same
+// name and effects are one InventoryItem with a count of 2.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct InventoryItem {
+    #[serde(default = "default_count")]
     count: u64,
     pub name: String,
-    pub effects: String,
+    pub effects: String, // what we tell the player when they use it
+    // what actually happens when they use it (see effect.rs)
+    #[serde(default)]
+    pub actions: Vec<Effect>,
+    // what's inside it, if it's something that can hold other items,
+    // like a bag. Items that aren't containers have None.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contents: Option<Vec<InventoryItem>>,
+    // a closed container has to be opened before anything can be
+    // seen, taken out of it or put in it
+    #[serde(default)]
+    pub closed: bool,
+    // and a locked one has to be unlocked before it can be opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<Lock>,
+    // the most damage it does when the player attacks with it, if
+    // it's a weapon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damage: Option<u64>,
+}
+
+// Why items couldn't be removed from a list.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Missing {
+    NotFound,
+    NotEnough { have: u64 },
 }
 
 impl InventoryItem {
@@ -11,6 +42,180 @@ impl InventoryItem {
             count: count,
             name: name,
             effects: effects,
+            actions: vec![],
+            contents: None,
+            closed: false,
+            lock: None,
+            damage: None,
+        }
+    }
+
+    // An empty item that other items can be put in.
+    pub fn container(name: String, effects: String) -> InventoryItem {
+        InventoryItem { contents: Some(vec![]), ..InventoryItem::new(1, name, effects) }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.lock.as_ref().map(|lock| lock.locked).unwrap_or(false)
+    }
+
+    // What's inside, if this is an open container.
+    pub fn open_contents(&self) -> Option<&Vec<InventoryItem>> {
+        if self.closed {
+            None
+        } else {
+            self.contents.as_ref()
+        }
+    }
+
+    pub fn open_contents_mut(&mut self) -> Option<&mut Vec<InventoryItem>> {
+        if self.closed {
+            None
+        } else {
+            self.contents.as_mut()
         }
     }
+
+    // Whether the player means this item when they say `name`. Case
+    // doesn't matter, and the plural works too, so "2 potions" finds
+    // a "potion".
+    pub fn is_called(&self, name: &str) -> bool {
+        called(&self.name, name)
+    }
+
+    // Whether `other` is the same kind of thing, so the two can be
+    // stacked together. Containers never stack, since each one has
+    // its own contents.
+    fn stacks_with(&self, other: &InventoryItem) -> bool {
+        self.contents.is_none() && other.contents.is_none() && self.name == other.name &&
+        self.effects == other.effects && self.actions == other.actions &&
+        self.closed == other.closed && self.lock == other.lock && self.damage == other.damage
+    }
+
+    // Take `count` off the top of this stack as a new stack.
+    fn split_off(&mut self, count: u64) -> InventoryItem {
+        self.count -= count;
+
+        let mut taken = self.clone();
+        taken.count = count;
+        taken
+    }
+}
+
+// Whether the player means an item called `own` when they say `name`
+// (see InventoryItem::is_called).
+pub fn called(own: &str, name: &str) -> bool {
+    let name = name.to_lowercase();
+    let own = own.to_lowercase();
+
+    name == own || name == format!("{}s", own) || name == format!("{}es", own)
+}
+
+// Put `item` in `items`, on top of a matching stack if there is one.
+pub fn add(items: &mut Vec<InventoryItem>, item: InventoryItem) {
+    if item.contents.is_some() {
+        items.extend(separate_containers(vec![item]));
+        return;
+    }
+
+    match items.iter_mut().find(|existing| existing.stacks_with(&item)) {
+        Some(existing) => existing.count = existing.count.saturating_add(item.count),
+        None => items.push(item),
+    }
+}
+
+// Split up any containers in `items` with a count of more than one,
+// like `"count": 2` bags in a world file, so each has its own
+// contents (see InventoryItem::stacks_with).
+pub fn separate_containers(items: Vec<InventoryItem>) -> Vec<InventoryItem> {
+    let mut separated = vec![];
+
+    for mut item in items {
+        item.contents = item.contents.take().map(separate_containers);
+
+        if item.contents.is_some() && item.count > 1 {
+            for _ in 0..item.count {
+                separated.push(InventoryItem { count: 1, ..item.clone() });
+            }
+        } else {
+            separated.push(item);
+        }
+    }
+
+    separated
+}
+
+// Take `count` of the item called `name` out of `items`, or the
+// whole stack if `count` is None.
+pub fn remove(items: &mut Vec<InventoryItem>,
+              name: &str,
+              count: Option<u64>)
+              -> Result<InventoryItem, Missing> {
+    let i = items.iter().position(|item| item.is_called(name)).ok_or(Missing::NotFound)?;
+    let have = items[i].count;
+
+    match count {
+        Some(count) if count > have => Err(Missing::NotEnough { have: have }),
+        Some(count) if count < have => Ok(items[i].split_off(count)),
+        _ => Ok(items.remove(i)),
+    }
+}
+
+// World files may leave out the count of an item, in which case
+// there is just one of it.
+fn default_count() -> u64 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn potion(count: u64) -> InventoryItem {
+        InventoryItem::new(count, "potion".to_string(), "yum".to_string())
+    }
+
+    #[test]
+    fn adding_matching_items_stacks_them() {
+        let mut items = vec![potion(1)];
+        add(&mut items, potion(2));
+        add(&mut items, InventoryItem::new(1, "potion".to_string(), "yuck".to_string()));
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].count(), 3);
+
+        // a stack can only get so big
+        add(&mut items, potion(u64::MAX));
+        assert_eq!(items[0].count(), u64::MAX);
+    }
+
+    #[test]
+    fn removing_takes_from_the_stack() {
+        let mut items = vec![potion(3)];
+
+        assert_eq!(remove(&mut items, "Potions", Some(2)), Ok(potion(2)));
+        assert_eq!(items, vec![potion(1)]);
+        assert_eq!(remove(&mut items, "potion", Some(2)),
+                   Err(Missing::NotEnough { have: 1 }));
+        assert_eq!(remove(&mut items, "hat", None), Err(Missing::NotFound));
+        assert_eq!(remove(&mut items, "potion", None), Ok(potion(1)));
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn containers_never_stack() {
+        let mut items = vec![];
+        add(&mut items, InventoryItem::container("bag".to_string(), String::new()));
+        add(&mut items, InventoryItem::container("bag".to_string(), String::new()));
+        let mut crates = InventoryItem::container("crate".to_string(), String::new());
+        crates.count = 2;
+        add(&mut items, crates);
+
+        assert_eq!(items.len(), 4);
+        assert!(items.iter().all(|item| item.count() == 1));
+    }
 }
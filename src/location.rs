@@ -1,6 +1,9 @@
 // We want to be able to debug Location, `==` it (which requires
-// Eq and PartialEq), use it as a HashMap key, and clone it.
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+// Eq and PartialEq), use it as a HashMap key, and clone it. We also
+// want to read and write it in world and save files, where it
+// defaults to 0, 0. And we want to sort it (by x, then y) so we can
+// list rooms in a stable order.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Default, Serialize, Deserialize)]
 pub struct Location {
     pub x: u64, // it has an x and y, both of which are unsigned numbers
     pub y: u64, // that means they cannot be negative (and we'll get an
@@ -1,148 +1,259 @@
 #![allow(dead_code)]
+// We spell out `name: name` in struct literals on purpose, to make it
+// clear where each field comes from.
+#![allow(clippy::redundant_field_names)]
 // #![feature(question_mark)]
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process;
+use validation::Severity;
+
 pub mod player;
 pub mod inventory_item;
 pub mod location;
 pub mod room;
 pub mod npc;
 pub mod game;
+pub mod command;
+pub mod dialogue;
+pub mod direction;
+pub mod exit;
+pub mod effect;
+pub mod event;
+pub mod quest;
+pub mod render;
+pub mod rng;
 pub mod map;
+pub mod save;
+pub mod transcript;
+pub mod validation;
 
 pub type StringLiteral = &'static str;
 
-// A helper function for easily contructing a room to pass to Map::new.
-// Used below in tests.
-pub fn room(x: u64,
-            y: u64,
-            name: StringLiteral,
-            desc: StringLiteral,
-            items: Vec<inventory_item::InventoryItem>,
-            npc: npc::NPC)
-            -> room::Room {
-    // In Rust, string literals are "slices", which means they are
-    // shared, but we want an owned String. We can use to_string()
-    // to copy the StringLiteral into something we can own.
-    room::Room::new(x, y, name.to_string(), desc.to_string(), items, npc)
-}
+// The world that ships with the game. It is compiled into the binary
+// so `cargo run` works from anywhere; pass `--world <file>` to play a
+// different one.
+const DEFAULT_WORLD: StringLiteral = include_str!("../worlds/great_rust_adventure.json");
 
 fn main() {
-    // vec![] is the Array literal syntax in Rust.
-    let rooms =
-        vec![room(0,
-                  2,
-                  "top left",
-                  "this is room one",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "cool potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           C00L d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("George".to_string(), vec![], "hi I'm George".to_string())),
-             room(1,
-                  2,
-                  "top center",
-                  "this is room two",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "dumb potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           dumbb d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Mike".to_string(), vec![], "hi I'm Mike".to_string())),
-             room(2,
-                  2,
-                  "top right",
-                  "this is room three",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "stinky potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           stinky d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Helen".to_string(), vec![], "hi I'm Helen".to_string())),
-             room(0,
-                  1,
-                  "middle left",
-                  "this is room four",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "charming potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           charming d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Linda".to_string(), vec![], "hi I'm Linda".to_string())),
-             room(1,
-                  1,
-                  "middle center",
-                  "this is room five",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "dog potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           C00L d0g!"
-                                                              .to_string())],
-                  npc::NPC::new("Prudence".to_string(),
-                                vec![inventory_item::InventoryItem::new(1,
-                                                                        "potato chip potion"
-                                                                            .to_string(),
-                                                                        "this potion has \
-                                                                         given you potato \
-                                                                         chips. You can't \
-                                                                         eat them, but \
-                                                                         they're there. \
-                                                                         LOOKING AT YOU."
-                                                                            .to_string())],
-                                "hi I'm Prudence".to_string())),
-             room(2,
-                  1,
-                  "middle right",
-                  "this is room six",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "barfing potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           barfing d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Fred".to_string(), vec![], "hi I'm Fred".to_string())),
-             room(0,
-                  0,
-                  "bottom left",
-                  "this is room seven",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "hungry potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           hungry d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Crocodile Man".to_string(),
-                                vec![],
-                                "hi I'm Crocodile Man".to_string())),
-             room(1,
-                  0,
-                  "bottom center",
-                  "this is room eight",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "cute potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           cute d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Crocodile Woman".to_string(),
-                                vec![],
-                                "hi I'm Crocodile Woman".to_string())),
-             room(2,
-                  0,
-                  "bottom right",
-                  "this is room nine",
-                  vec![inventory_item::InventoryItem::new(1,
-                                                          "tall potion".to_string(),
-                                                          "this potion has turned you into a \
-                                                           tall d00d!"
-                                                              .to_string())],
-                  npc::NPC::new("Cool Unicorn".to_string(),
-                                vec![],
-                                "hi I'm Cool Unicorn".to_string()))];
-
-    let player = player::Player::new(vec![], 1, 1);
-    let map = map::Map::new("Great Rust Adventure", rooms);
-    let mut game = game::Game::new(player, map, true);
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // `adventure_game check-world [file]` checks a world file for
+    // mistakes without playing it.
+    if args.first().map(|arg| arg == "check-world").unwrap_or(false) {
+        let map = load_world(args.get(1));
+        process::exit(check_world(&map));
+    }
+
+    // `--replay <file>` plays a transcript made with `--record` and
+    // checks that the game still says the same things.
+    if let Some(path) = flag(&args, "--replay") {
+        process::exit(replay(Path::new(path)));
+    }
+
+    let world = flag(&args, "--world");
+    let map = load_world(world);
+
+    // `--seed <number>` makes everything random (like where NPCs
+    // wander) come out the same every time, for tests and bug reports
+    let seed = flag(&args, "--seed").map(|seed| match seed.parse() {
+        Ok(seed) => seed,
+        Err(_) => {
+            eprintln!("--seed needs a number, not {:?}", seed);
+            process::exit(1);
+        }
+    });
+
+    if !playable(&map) {
+        process::exit(1);
+    }
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let name = ask_name(&mut input).expect("Could not read line");
+
+    let header = transcript::Header {
+        seed: seed.unwrap_or_else(|| rng::Rng::from_time().seed()),
+        // the full path, so the transcript can be replayed from
+        // anywhere
+        world: world.map(|path| match fs::canonicalize(path) {
+            Ok(path) => path.display().to_string(),
+            Err(_) => path.clone(),
+        }),
+        title: map.title.clone(),
+        player: name.clone(),
+    };
+
+    let player = player::Player::starting_in(name, &map);
+    let mut game = game::Game::new(player, map, true, input, io::stdout());
+    game.rng = rng::Rng::new(header.seed);
+
+    let result = match flag(&args, "--record") {
+        Some(path) => record(&mut game, Path::new(path), &header),
+        None => game.run(),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+// The value after `name` in the command line arguments, like the
+// file in `--world worlds/mine.json`.
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter().position(|arg| arg == name).map(|i| match args.get(i + 1) {
+        Some(value) => value,
+        None => {
+            eprintln!("{} needs a value", name);
+            process::exit(1);
+        }
+    })
+}
+
+// Play `game`, writing everything that happens to a transcript at
+// `path` as it happens.
+fn record<R: BufRead, W: Write>(game: &mut game::Game<R, W>,
+                                path: &Path,
+                                header: &transcript::Header)
+                                -> io::Result<()> {
+    let mut file = File::create(path)?;
+    transcript::write_line(&mut file, header)?;
+    game.recording = Some(vec![]);
+    game.welcome()?;
 
     while game.playing {
-        game.play();
+        game.play()?;
+        for step in game.recording.as_mut().map(|steps| steps.drain(..)).into_iter().flatten() {
+            transcript::write_line(&mut file, &step)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Play the transcript at `path` and return the exit code: 0 if the
+// game said everything it said when it was recorded, 1 otherwise.
+fn replay(path: &Path) -> i32 {
+    let (header, steps) = match transcript::read(path) {
+        Ok(transcript) => transcript,
+        Err(err) => {
+            eprintln!("could not read transcript: {}", err);
+            return 1;
+        }
+    };
+
+    let map = load_world(header.world.as_ref());
+    if map.title != header.title {
+        eprintln!("the transcript was recorded in {:?}, but this is {:?}",
+                  header.title,
+                  map.title);
+        return 1;
+    }
+    if !playable(&map) {
+        return 1;
+    }
+
+    let player = player::Player::starting_in(header.player, &map);
+    let mut game = game::Game::new(player, map, true, io::empty(), io::sink());
+    game.rng = rng::Rng::new(header.seed);
+
+    match transcript::replay(&mut game, &steps) {
+        Ok(count) => {
+            println!("Replayed {} steps with no differences.", count);
+            0
+        }
+        Err(divergence) => {
+            println!("Step {} ({:?}) came out differently.", divergence.step, divergence.input);
+            println!("Expected:\n{}", divergence.expected);
+            println!("Got:\n{}", divergence.got);
+            1
+        }
+    }
+}
+
+// Print any problems with the map, and say whether it can be played.
+// Warnings are worth knowing about, but only errors (which would
+// crash the game later) stop us from playing.
+fn playable(map: &map::Map) -> bool {
+    let diagnostics = map.validate();
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    !diagnostics.iter().any(|d| d.severity() == Severity::Error)
+}
+
+fn ask_name<R: BufRead>(input: &mut R) -> io::Result<String> {
+    // get user input and assign the input to `name`
+    println!("What's your name?");
+
+    let mut name = String::new();
+    input.read_line(&mut name)?;
+
+    Ok(name.trim().to_string())
+}
+
+// Load the world file at `path`, or the bundled world if there is
+// no path. If the world can't be loaded there's no game to play, so
+// print the error and exit.
+fn load_world(path: Option<&String>) -> map::Map {
+    let map = match path {
+        Some(path) => map::Map::from_file(path),
+        None => DEFAULT_WORLD.parse(),
+    };
+
+    match map {
+        Ok(map) => map,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+// Print every problem with the map and return the exit code for
+// `check-world`: 0 if there were no errors, 1 otherwise.
+fn check_world(map: &map::Map) -> i32 {
+    let diagnostics = map.validate();
+
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.severity() == Severity::Error).count();
+    println!("{}: {} error(s), {} warning(s)",
+             map.title,
+             errors,
+             diagnostics.len() - errors);
+
+    if errors == 0 { 0 } else { 1 }
+}
+
+trait Chomp {
+    fn chomp(&self) -> &str;
+}
+
+impl Chomp for str {
+    fn chomp(&self) -> &str {
+        if self.ends_with('\n') {
+            &self[0..self.len() - 1]
+        } else {
+            self
+        }
+    }
+}
+
+impl Chomp for String {
+    fn chomp(&self) -> &str {
+        self[..].chomp()
     }
 }
 
@@ -151,6 +262,21 @@ fn main() {
 mod tests {
     // this is a nested module
     use super::*; // include all the public items from the parent module
+    use location::Location;
+    use direction::Direction;
+    use exit::Exit;
+    use map::Map;
+    use room::Room;
+
+    // helper function for constructing an empty room with a name
+    fn room(x: u64, y: u64, name: StringLiteral) -> Room {
+        Room::new(x,
+                  y,
+                  name.to_string(),
+                  String::new(),
+                  vec![],
+                  vec![])
+    }
 
     // helper function for constructing a 3x3 list of rooms for testing
     fn rooms() -> Vec<Room> {
@@ -168,7 +294,12 @@ mod tests {
     // helper function for constructing a room whose description is
     // just its `x, y` coordinates.
     fn simple_room(x: u64, y: u64) -> Room {
-        Room::new(x, y, format!("{}, {}", x, y))
+        Room::new(x,
+                  y,
+                  format!("{}, {}", x, y),
+                  String::new(),
+                  vec![],
+                  vec![])
     }
 
     // helper function for constructing a 4x4 list of rooms.
@@ -208,12 +339,7 @@ mod tests {
         // If the two sides are not equal, panic and fail the
         // test. This assumes both sides are Eq and PartialEq.
         assert_eq!(valid_directions,
-                   ValidDirection {
-                       north: true,
-                       south: false,
-                       east: true,
-                       west: false,
-                   });
+                   vec![Direction::North, Direction::East]);
     }
 
     #[test]
@@ -221,8 +347,12 @@ mod tests {
         let map = map();
         // Indexing a HashMap does not take ownership of the value
         // passed to `[]`, so we lend the Location we create here
-        // by using `&`.
-        assert_eq!(map.rooms[&Location::new(0, 2)], rooms()[0]);
+        // by using `&`. Map::new gives the room exits to its
+        // neighbours, so give the room we compare against them too.
+        let mut top_left = rooms().remove(0);
+        top_left.exits.insert(Direction::South, Exit::new(Location::new(0, 1)));
+        top_left.exits.insert(Direction::East, Exit::new(Location::new(1, 2)));
+        assert_eq!(map.rooms[&Location::new(0, 2)], top_left);
     }
 
     #[test]
@@ -232,12 +362,7 @@ mod tests {
         let valid_directions = map.valid_directions(&location);
 
         assert_eq!(valid_directions,
-                   ValidDirection {
-                       north: false,
-                       south: true,
-                       east: false,
-                       west: true,
-                   });
+                   vec![Direction::South, Direction::West]);
     }
 
     #[test]
@@ -247,33 +372,69 @@ mod tests {
         let valid_directions = map.valid_directions(&location);
 
         assert_eq!(valid_directions,
-                   ValidDirection {
-                       north: false,
-                       south: true,
-                       east: false,
-                       west: true,
-                   });
+                   vec![Direction::South, Direction::West]);
     }
-}
 
+    #[test]
+    fn valid_directions_follow_declared_exits() {
+        let mut rooms = rooms();
+        // the bottom left room has a wall to the east, and a trapdoor
+        // down into the middle of the map
+        rooms[6].exits.insert(Direction::North, Exit::new(Location::new(0, 1)));
+        rooms[6].exits.insert(Direction::Down, Exit::new(Location::new(1, 1)));
+        let map = Map::new("Liz's Great Adventure", rooms);
 
+        assert_eq!(map.valid_directions(&Location::new(0, 0)),
+                   vec![Direction::North, Direction::Down]);
+        assert_eq!(map.exit(&Location::new(0, 0), Direction::Down).map(|exit| &exit.to),
+                   Some(&Location::new(1, 1)));
+        // rooms that didn't declare exits still connect to the grid,
+        // even if that leads somewhere with no way back
+        assert_eq!(map.valid_directions(&Location::new(1, 0)),
+                   vec![Direction::North, Direction::East, Direction::West]);
+    }
 
-trait Chomp {
-    fn chomp(&self) -> &str;
-}
+    #[test]
+    fn draws_the_map_as_a_grid() {
+        let mut kitchen = room(1, 0, "kitchen");
+        kitchen.exits.insert(Direction::West, Exit::new(Location::new(0, 0)));
+        kitchen.exits.insert(Direction::Up, Exit::new(Location::new(1, 1)));
+        let mut attic = room(1, 1, "attic");
+        attic.exits.insert(Direction::Down, Exit::new(Location::new(1, 0)));
+        // nothing at 0, 1, and the attic is only reachable by the stairs
+        let map = Map::new("House", vec![room(0, 0, "hall"), kitchen, attic]);
 
-impl Chomp for str {
-    fn chomp(&self) -> &str {
-        if self.ends_with('\n') {
-            &self[0..self.len() - 1]
-        } else {
-            self
-        }
+        assert_eq!(map.grid(Some(&Location::new(0, 0)), None),
+                   ["                +---------------+",
+                    "                |     attic     |",
+                    "                |       v       |",
+                    "+---------------+---------------+",
+                    "|     hall           kitchen    |",
+                    "|       @               ^       |",
+                    "+---------------+---------------+",
+                    "@ you are here, ^ stairs up, v stairs down"]
+                       .join("\n"));
+        assert!(map.to_string().starts_with("House\n=====\n                +----"));
+
+        // from the hall, the player can see the way into the kitchen,
+        // but not what's in it or upstairs
+        let visited = vec![Location::new(0, 0)].into_iter().collect();
+        assert_eq!(map.grid(Some(&Location::new(0, 0)), Some(&visited)),
+                   ["+---------------+---------------+",
+                    "|     hall              ?       |",
+                    "|       @                       |",
+                    "+---------------+---------------+",
+                    "@ you are here, ? somewhere you haven't been"]
+                       .join("\n"));
     }
-}
 
-impl Chomp for String {
-    fn chomp(&self) -> &str {
-        self[..].chomp()
+    #[test]
+    fn loads_the_bundled_world() {
+        let map: Map = DEFAULT_WORLD.parse().expect("the bundled world should parse");
+
+        assert_eq!(map.title, "Great Rust Adventure");
+        assert_eq!(map.rooms.len(), 9);
+        assert_eq!(map.start, Location::new(1, 1));
+        assert_eq!(map.rooms[&Location::new(1, 1)].npcs[0].inventory.len(), 1);
     }
 }
@@ -1,30 +1,137 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::cmp::max;
+use std::error::Error;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use serde_json;
+use direction::Direction;
+use exit::Exit;
+use inventory_item;
 use location::Location;
+use quest::{Quest, Win};
 use room::Room;
+use validation::{self, Diagnostic};
 
 pub type StringLiteral = &'static str;
 
-// We want to be able to debug Room and `==` it.
-#[derive(Debug, Eq, PartialEq)]
+// We want to be able to debug Room and `==` it. A Map can also be
+// read out of a world file and written to a save file, both of
+// which look like MapFile below.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(from = "MapFile", into = "MapFile")]
 pub struct Map {
     pub title: String,
     pub rooms: HashMap<Location, Room>,
+    pub start: Location, // where the player begins
+    // the name of the item NPCs take as money, if the world has
+    // money (see npc::Trade)
+    pub currency: Option<String>,
+    // what there is for the player to do, and how much of it they
+    // have done (see quest.rs)
+    pub quests: Vec<Quest>,
+    pub win: Option<Win>, // how the player wins, if they can
+    // the most health the player can have, if it isn't the usual
+    // player::MAX_HEALTH
+    pub max_health: Option<u64>,
+    // the stats the world keeps track of, like "coolness", and what
+    // the player starts with
+    pub stats: BTreeMap<String, i64>,
     max_x: u64, // east-most room
     max_y: u64, // north-most room
+    // rooms that were replaced by a later room with the same
+    // Location, kept around so Map::validate can report them
+    duplicates: Vec<Room>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct ValidDirection {
-    pub north: bool,
-    pub south: bool,
-    pub east: bool,
-    pub west: bool,
+// The shape of a world file on disk. JSON object keys must be
+// strings, so rooms are stored as a list (each room already knows
+// its own Location) and Map::new turns them into a HashMap.
+#[derive(Serialize, Deserialize)]
+struct MapFile {
+    title: String,
+    #[serde(default)]
+    start: Location,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    quests: Vec<Quest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    win: Option<Win>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_health: Option<u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    stats: BTreeMap<String, i64>,
+    rooms: Vec<Room>,
+}
+
+impl From<MapFile> for Map {
+    fn from(file: MapFile) -> Map {
+        let mut map = Map::new(&file.title, file.rooms);
+        map.start = file.start;
+        map.currency = file.currency;
+        map.quests = file.quests;
+        map.win = file.win;
+        map.max_health = file.max_health;
+        map.stats = file.stats;
+        map
+    }
+}
+
+impl From<Map> for MapFile {
+    fn from(map: Map) -> MapFile {
+        // Sort the rooms so that saving the same map twice writes
+        // the same file.
+        let mut rooms: Vec<Room> = map.rooms.into_values().collect();
+        rooms.sort_by_key(|room| room.location.clone());
+
+        MapFile {
+            title: map.title,
+            start: map.start,
+            currency: map.currency,
+            quests: map.quests,
+            win: map.win,
+            max_health: map.max_health,
+            stats: map.stats,
+            rooms: rooms,
+        }
+    }
+}
+
+// Everything that can go wrong when loading a world file.
+#[derive(Debug)]
+pub enum WorldError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            WorldError::Io(ref err) => write!(f, "could not read world file: {}", err),
+            WorldError::Parse(ref err) => write!(f, "could not parse world file: {}", err),
+        }
+    }
+}
+
+impl Error for WorldError {}
+
+impl From<io::Error> for WorldError {
+    fn from(err: io::Error) -> WorldError {
+        WorldError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for WorldError {
+    fn from(err: serde_json::Error) -> WorldError {
+        WorldError::Parse(err)
+    }
 }
 
 impl Map {
-    pub fn new(title: StringLiteral, room_list: Vec<Room>) -> Map {
+    pub fn new(title: &str, room_list: Vec<Room>) -> Map {
         // Make a new mutable HashMap
         let mut rooms = HashMap::new();
 
@@ -32,8 +139,18 @@ impl Map {
         let mut max_x = 0;
         let mut max_y = 0;
 
+        // Rooms that get overwritten because they share a Location
+        let mut duplicates = vec![];
+
         // Iterate over the room_list
-        for room in room_list {
+        for mut room in room_list {
+            // every container gets its own contents
+            room.items = inventory_item::separate_containers(room.items);
+            for npc in &mut room.npcs {
+                let inventory = npc.inventory.drain(..).collect();
+                npc.inventory = inventory_item::separate_containers(inventory);
+            }
+
             // extract x and y from the room's location
             let x = room.location.x;
             let y = room.location.y;
@@ -45,67 +162,378 @@ impl Map {
             // insert the room into the rooms Hash with its location
             // as a key. clone the location because removing the
             // location from the room to use as a key prevents use
-            // from using the room as a value.
-            rooms.insert(room.location.clone(), room);
+            // from using the room as a value. If there was already
+            // a room there, hold on to it so we can complain later.
+            if let Some(replaced) = rooms.insert(room.location.clone(), room) {
+                duplicates.push(replaced);
+            }
+        }
+
+        // Rooms that don't say where their exits go get one exit
+        // for each neighbouring room on the grid, which is how the
+        // game worked before rooms had exits. This keeps simple,
+        // rectangular worlds simple to write.
+        let grid_exits: Vec<(Location, Vec<(Direction, Exit)>)> = rooms.values()
+            .filter(|room| room.exits.is_empty())
+            .map(|room| (room.location.clone(), grid_neighbours(&rooms, &room.location)))
+            .collect();
+
+        for (location, exits) in grid_exits {
+            if let Some(room) = rooms.get_mut(&location) {
+                room.exits.extend(exits);
+            }
         }
 
         // construct a new Map
         Map {
             title: title.to_string(),
             rooms: rooms,
+            start: Location::default(),
+            currency: None,
+            quests: vec![],
+            win: None,
+            max_health: None,
+            stats: BTreeMap::new(),
             max_x: max_x,
             max_y: max_y,
+            duplicates: duplicates,
         }
     }
 
+    pub fn max_x(&self) -> u64 {
+        self.max_x
+    }
+
+    pub fn max_y(&self) -> u64 {
+        self.max_y
+    }
+
+    pub fn duplicates(&self) -> &[Room] {
+        &self.duplicates[..]
+    }
+
+    // Check the map for authoring mistakes, like two rooms in the
+    // same place or rooms the player can never get to. An empty
+    // list means the map is fine.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validation::validate(self)
+    }
+
+    // Read a world file from disk. See worlds/ for an example.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Map, WorldError> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        contents.parse()
+    }
+
     // valid_directions takes a Location contained in this
-    // Map and answers which directions a player can go.
-    // Specifically, a player is not allowed to move off
-    // the edge of the map.
-    pub fn valid_directions(&self, l: &Location) -> ValidDirection {
-        ValidDirection {
-            // identical to the Ruby code
-            north: l.y < self.max_y,
-            south: l.y > 0,
-            east: l.x < self.max_x,
-            west: l.x > 0,
+    // Map and answers which directions a player can go,
+    // which is whichever ones the room there has (visible)
+    // exits for.
+    pub fn valid_directions(&self, l: &Location) -> Vec<Direction> {
+        Direction::all()
+            .iter()
+            .filter(|&&dir| self.exit(l, dir).is_some())
+            .cloned()
+            .collect()
+    }
+
+    // The exit going `dir` from `l`, if there is one the player
+    // knows about. Hidden exits don't count until they're revealed.
+    pub fn exit(&self, l: &Location, dir: Direction) -> Option<&Exit> {
+        self.rooms
+            .get(l)
+            .and_then(|room| room.exits.get(&dir))
+            .filter(|exit| !exit.hidden)
+    }
+
+    // The direction of the exit from `from` that leads to `to`, if
+    // there is one the player knows about.
+    pub fn direction_between(&self, from: &Location, to: &Location) -> Option<Direction> {
+        Direction::all()
+            .iter()
+            .find(|&&dir| self.exit(from, dir).map(|exit| exit.to == *to).unwrap_or(false))
+            .cloned()
+    }
+
+    // Unlock the exit going `dir` from `l`, and report whether there
+    // was a lock to open. Doors have two sides, so if the room on the
+    // other side has a locked exit back to `l` that takes the same
+    // key, that is unlocked too.
+    pub fn unlock(&mut self, l: &Location, dir: Direction) -> bool {
+        let (to, key) = match self.rooms.get_mut(l).and_then(|room| room.exits.get_mut(&dir)) {
+            Some(&mut Exit { ref to, lock: Some(ref mut lock), .. }) if lock.locked => {
+                lock.locked = false;
+                (to.clone(), lock.key.clone())
+            }
+            _ => return false,
+        };
+
+        let other_side = self.rooms.get_mut(&to);
+        for exit in other_side.into_iter().flat_map(|room| room.exits.values_mut()) {
+            if exit.to == *l {
+                if let Some(ref mut lock) = exit.lock {
+                    if lock.key == key {
+                        lock.locked = false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn exit_mut(&mut self, l: &Location, dir: Direction) -> Option<&mut Exit> {
+        self.rooms
+            .get_mut(l)
+            .and_then(|room| room.exits.get_mut(&dir))
+            .filter(|exit| !exit.hidden)
+    }
+
+    // The map's title, underlined, over its grid. This is what
+    // `display map` shows, and what `Display` prints.
+    pub fn draw(&self, here: Option<&Location>, visited: Option<&BTreeSet<Location>>) -> String {
+        let underline = "=".repeat(self.title.chars().count());
+        format!("{}\n{}\n{}", self.title, underline, self.grid(here, visited))
+    }
+
+    // The map drawn in ASCII, north at the top. Each room is a box
+    // with its name in it, with a gap in the wall wherever there's a
+    // way through to the next room. Places on the grid with no room
+    // are left blank. Under each name is where the player is (`here`)
+    // and any stairs, like
+    //
+    //                     +---------------+
+    //                     |     attic     |
+    //                     |       v       |
+    //     +---------------+---------------+
+    //     |     hall           kitchen    |
+    //     |       @               ^       |
+    //     +---------------+---------------+
+    //     @ you are here, ^ stairs up, v stairs down
+    //
+    // If there's a list of rooms the player has `visited`, only those
+    // are drawn, along with any rooms they've seen a way into, which
+    // show up as `?`. Otherwise the whole map is.
+    pub fn grid(&self, here: Option<&Location>, visited: Option<&BTreeSet<Location>>) -> String {
+        let known = self.known_rooms(visited);
+        let mut lines = vec![];
+        let mut marked = vec![];
+
+        for y in (0..self.max_y + 1).rev() {
+            lines.push(self.grid_border(&known, y + 1));
+
+            let mut names = String::new();
+            let mut marks = String::new();
+            for x in 0..self.max_x + 2 {
+                let west = x.checked_sub(1).and_then(|x| known_at(&known, x, y));
+                let room = known_at(&known, x, y);
+                let wall = self.grid_wall(west, room, Direction::East, "|", " ");
+                names.push_str(&wall);
+                marks.push_str(&wall);
+
+                if x > self.max_x {
+                    break;
+                }
+                let room = match room {
+                    Some((room, true)) => room,
+                    Some((_, false)) => {
+                        marked.push('?');
+                        names.push_str(&format!("{:^1$}", "?", GRID_CELL_WIDTH));
+                        marks.push_str(&" ".repeat(GRID_CELL_WIDTH));
+                        continue;
+                    }
+                    None => {
+                        names.push_str(&" ".repeat(GRID_CELL_WIDTH));
+                        marks.push_str(&" ".repeat(GRID_CELL_WIDTH));
+                        continue;
+                    }
+                };
+
+                let name: String = room.name.chars().take(GRID_CELL_WIDTH - 2).collect();
+                names.push_str(&format!("{:^1$}", name, GRID_CELL_WIDTH));
+
+                let mut mark = String::new();
+                if here == Some(&room.location) {
+                    mark.push('@');
+                }
+                for &(symbol, dir, _) in GRID_STAIRS.iter() {
+                    if self.exit(&room.location, dir).is_some() {
+                        mark.push(symbol);
+                    }
+                }
+                marked.extend(mark.chars());
+                marks.push_str(&format!("{:^1$}", mark, GRID_CELL_WIDTH));
+            }
+
+            lines.push(names.trim_end().to_string());
+            lines.push(marks.trim_end().to_string());
+        }
+        // the wall along the bottom of row 0
+        lines.push(self.grid_border(&known, 0));
+        // and rows with nothing the player knows about at the top or
+        // bottom of the map would just be empty lines
+        let last = lines.iter().rposition(|line| !line.is_empty()).map_or(0, |i| i + 1);
+        lines.truncate(last);
+        let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(0);
+        lines.drain(..first);
+
+        // only explain the symbols that are actually on the map
+        let mut legend = vec![];
+        if marked.contains(&'@') {
+            legend.push("@ you are here".to_string());
+        }
+        for &(symbol, _, meaning) in GRID_STAIRS.iter() {
+            if marked.contains(&symbol) {
+                legend.push(format!("{} {}", symbol, meaning));
+            }
+        }
+        if marked.contains(&'?') {
+            legend.push("? somewhere you haven't been".to_string());
+        }
+        if !legend.is_empty() {
+            lines.push(legend.join(", "));
+        }
+
+        lines.join("\n")
+    }
+
+    // The rooms the grid shows, and whether the player has been to
+    // each one (see Map::grid).
+    fn known_rooms(&self,
+                   visited: Option<&BTreeSet<Location>>)
+                   -> HashMap<&Location, (&Room, bool)> {
+        let visited = match visited {
+            Some(visited) => visited,
+            None => return self.rooms.iter().map(|(l, room)| (l, (room, true))).collect(),
+        };
+
+        let mut known = HashMap::new();
+        for room in self.rooms.values().filter(|room| visited.contains(&room.location)) {
+            known.insert(&room.location, (room, true));
+
+            for dir in self.valid_directions(&room.location) {
+                let to = self.exit(&room.location, dir).map(|exit| &exit.to);
+                if let Some((l, next)) = to.and_then(|to| self.rooms.get_key_value(to)) {
+                    known.entry(l).or_insert((next, false));
+                }
+            }
         }
+        known
     }
 
-    pub fn display_map(&self) {
-        println!("Possible Destinations for {}", self.title);
-        println!("{:=<1$}", "", self.title.len() + 26);
-        for room_info in &self.rooms {
-            println!("{}", room_info.1.name);
+    // The line of the grid between row `y` and the row below it.
+    fn grid_border(&self, known: &HashMap<&Location, (&Room, bool)>, y: u64) -> String {
+        let mut line = String::new();
+
+        for x in 0..self.max_x + 2 {
+            let below = y.checked_sub(1);
+            // a corner is drawn if any of the four places around it is a room
+            let around = [(x.checked_sub(1), Some(y)),
+                          (Some(x), Some(y)),
+                          (x.checked_sub(1), below),
+                          (Some(x), below)];
+            let corner = around.iter().any(|&place| match place {
+                (Some(x), Some(y)) => known_at(known, x, y).is_some(),
+                _ => false,
+            });
+            line.push(if corner { '+' } else { ' ' });
+
+            if x > self.max_x {
+                break;
+            }
+            // a gap in the middle of the wall, four spaces wide
+            let left = (GRID_CELL_WIDTH - 4) / 2;
+            let right = GRID_CELL_WIDTH - 4 - left;
+            let open = format!("{}    {}", "-".repeat(left), "-".repeat(right));
+            line.push_str(&self.grid_wall(known_at(known, x, y),
+                                          below.and_then(|y| known_at(known, x, y)),
+                                          Direction::South,
+                                          &"-".repeat(GRID_CELL_WIDTH),
+                                          &open));
+        }
+
+        line.trim_end().to_string()
+    }
+
+    // The wall between two places on the grid, where `b` is `dir`
+    // from `a`: `open` if there's an exit through it either way,
+    // nothing if neither place is a room, and `solid` otherwise. Only
+    // the exits of rooms the player has been to count.
+    fn grid_wall(&self,
+                 a: Option<(&Room, bool)>,
+                 b: Option<(&Room, bool)>,
+                 dir: Direction,
+                 solid: &str,
+                 open: &str)
+                 -> String {
+        let leads_to = |(from, visited): (&Room, bool), dir: Direction, (to, _): (&Room, bool)| {
+            visited &&
+            self.exit(&from.location, dir).map(|exit| exit.to == to.location).unwrap_or(false)
+        };
+
+        match (a, b) {
+            (None, None) => " ".repeat(solid.len()),
+            (Some(a), Some(b)) if leads_to(a, dir, b) || leads_to(b, dir.opposite(), a) => {
+                open.to_string()
+            }
+            _ => solid.to_string(),
         }
     }
 }
 
-impl fmt::Display for Map {
-    fn fmt(&self, _f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        // writeln!(_f, "{}", self.title)?;
+// The room the grid shows at `x, y`, if there is one (see
+// Map::known_rooms).
+fn known_at<'a>(known: &HashMap<&Location, (&'a Room, bool)>,
+                x: u64,
+                y: u64)
+                -> Option<(&'a Room, bool)> {
+    known.get(&Location::new(x, y)).cloned()
+}
 
-        // for _ in self.title.chars() {
-        //     write!(_f, "=")?;
-        // }
+// How wide each room is on the grid (see Map::grid). Longer names
+// are cut short.
+const GRID_CELL_WIDTH: usize = 15;
 
-        // write!(_f, "\n\n")?;
+// How the grid shows exits that aren't north, south, east or west.
+const GRID_STAIRS: [(char, Direction, &str); 4] = [('^', Direction::Up, "stairs up"),
+                                                   ('v', Direction::Down, "stairs down"),
+                                                   ('>', Direction::In, "a way in"),
+                                                   ('<', Direction::Out, "a way out")];
 
-        // for row in &self.rooms {
-        //     for room in row {
-        //         write!(_f, "{:20} ", room.description)?;
-        //     }
+// The rooms next to `l` on the grid, and the direction of each.
+fn grid_neighbours(rooms: &HashMap<Location, Room>, l: &Location) -> Vec<(Direction, Exit)> {
+    let mut neighbours = vec![(Direction::North, Location::new(l.x, l.y + 1)),
+                              (Direction::East, Location::new(l.x + 1, l.y))];
+
+    // Locations can't be negative, so there is nothing south of
+    // y = 0 or west of x = 0.
+    if l.y > 0 {
+        neighbours.push((Direction::South, Location::new(l.x, l.y - 1)));
+    }
+    if l.x > 0 {
+        neighbours.push((Direction::West, Location::new(l.x - 1, l.y)));
+    }
 
-        //     write!(_f, "\n")?;
+    neighbours.into_iter()
+        .filter(|(_, to)| rooms.contains_key(to))
+        .map(|(dir, to)| (dir, Exit::new(to)))
+        .collect()
+}
 
-        //     for room in row {
-        //         write!(_f, "{:20} ",
-        //                format!("{}, {}", room.location.x, room.location.y))?;
-        //     }
+// Lets us write `json.parse::<Map>()` for world files that are
+// already in memory, like the bundled default world.
+impl FromStr for Map {
+    type Err = WorldError;
 
-        //     write!(_f, "\n")?;
-        // }
+    fn from_str(s: &str) -> Result<Map, WorldError> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
 
-        Ok(())
+// The whole map, the way `display map` shows it (see Map::draw).
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.draw(None, None))
     }
 }
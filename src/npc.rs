@@ -1,11 +1,97 @@
-use inventory_item::InventoryItem;
+use dialogue::{Condition, DialogueTree};
+use inventory_item::{self, InventoryItem};
+use location::Location;
 
 // a non-player character
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct NPC {
     pub name: String,
+    #[serde(default)]
     pub inventory: Vec<InventoryItem>,
-    pub dialogue: String,
+    pub dialogue: String, // what they say if they don't have a tree
+    // a conversation the player can have with them (see dialogue.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree: Option<DialogueTree>,
+    // what they're willing to trade. Items they have a trade for
+    // can't just be taken from them.
+    #[serde(default)]
+    pub trades: Vec<Trade>,
+    // what they do each turn (see Game::tick)
+    #[serde(default)]
+    pub behaviour: Behaviour,
+    // when they're around, if they aren't always
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+    // whether they're off somewhere because of their schedule. NPCs
+    // who are away can't be seen, talked to or traded with.
+    #[serde(default)]
+    pub away: bool,
+    // how they fight, if they can be fought
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fight: Option<Fighter>,
+}
+
+// How an NPC gets around. In world files this is "stay", "wander",
+// "follow" or `{ "patrol": [{ "x": 0, "y": 0 }, { "x": 1, "y": 0 }] }`.
+#[derive(Debug, Eq, PartialEq, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Behaviour {
+    // never leave their room
+    #[default]
+    Stay,
+    // now and then, wander off through one of the room's exits
+    Wander,
+    // go to the next room on the route every turn, going back to
+    // the start after the end. Each room should only be on the route
+    // once, since the NPC goes to whatever comes after the first time
+    // their room shows up.
+    Patrol(Vec<Location>),
+    // follow the player out of the room
+    Follow,
+}
+
+// What an NPC fights with, like
+//
+//     "fight": { "health": 20, "attack": 4, "hostile": true }
+//
+// Hostile NPCs attack the player at the end of every turn they're in
+// the same room, doing up to `attack` damage. NPCs the player attacks
+// become hostile, if they weren't already.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Fighter {
+    pub health: u64,
+    #[serde(default)]
+    pub attack: u64,
+    #[serde(default)]
+    pub hostile: bool,
+}
+
+// The turns an NPC is around for, from `from` until (but not
+// including) `until`, or forever if there's no `until`.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub from: u64,
+    #[serde(default)]
+    pub until: Option<u64>,
+}
+
+// One of an NPC's items, and what they want for it, like
+//
+//     { "gives": "hat", "wants": "potion", "price": 2 }
+//
+// Leaving out `wants` means they want the world's currency (see
+// Map::currency). Trades can have `conditions`, like dialogue
+// responses, and the NPC refuses to trade unless they all hold.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub gives: String,
+    #[serde(default)]
+    pub wants: Option<String>,
+    #[serde(default = "default_price")]
+    pub price: u64, // how many of `wants` they want
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
 }
 
 impl NPC {
@@ -14,9 +100,28 @@ impl NPC {
             name: name,
             inventory: inventory,
             dialogue: dialogue,
+            tree: None,
+            trades: vec![],
+            behaviour: Behaviour::Stay,
+            schedule: None,
+            away: false,
+            fight: None,
         }
     }
 
+    pub fn is_hostile(&self) -> bool {
+        self.fight.as_ref().map(|fight| fight.hostile).unwrap_or(false)
+    }
+
+    pub fn is_called(&self, name: &str) -> bool {
+        self.name.to_lowercase() == name.to_lowercase()
+    }
+
+    // The trade the NPC has for the item the player calls `item`.
+    pub fn trade_for(&self, item: &str) -> Option<&Trade> {
+        self.trades.iter().find(|trade| inventory_item::called(&trade.gives, item))
+    }
+
     fn inventory(&self) -> &[InventoryItem] {
         &self.inventory[..]
     }
@@ -25,3 +130,13 @@ impl NPC {
         &mut self.inventory
     }
 }
+
+impl Schedule {
+    pub fn includes(&self, turn: u64) -> bool {
+        turn >= self.from && self.until.map(|until| turn < until).unwrap_or(true)
+    }
+}
+
+fn default_price() -> u64 {
+    1
+}
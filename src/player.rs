@@ -1,43 +1,67 @@
-use std::io;
-use inventory_item::InventoryItem;
+use std::collections::{BTreeMap, BTreeSet};
+use inventory_item::{self, InventoryItem};
 use location::Location;
+use map::Map;
 
-#[derive(Debug, Eq, PartialEq)]
+// How healthy a player is when they start, and the most health
+// they can ever have, unless the world says otherwise (see
+// Map::max_health).
+pub const MAX_HEALTH: u64 = 100;
+
+// Players are written to save files along with the map.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub name: String,
     inventory: Vec<InventoryItem>,
     pub location: Location,
+    pub health: u64, // when this gets to 0, it's game over
+    #[serde(default = "default_max_health")]
+    pub max_health: u64,
+    // numbers the world keeps track of, like "coolness" (see
+    // Map::stats). Item effects and hazards change them.
+    #[serde(default)]
+    pub stats: BTreeMap<String, i64>,
+    // things that are true about the player, like "C00L d00d", set
+    // by using items. A BTreeSet keeps them in order in save files.
+    pub flags: BTreeSet<String>,
+    // the rooms the player has been to, which are the ones the map
+    // shows them (see Map::grid)
+    #[serde(default)]
+    pub visited: BTreeSet<Location>,
+    // whether the player has seen a map of the whole world, so the
+    // map shows them every room (see Effect::RevealMap)
+    #[serde(default)]
+    pub map_revealed: bool,
 }
 
 impl Player {
-    pub fn new(inventory: Vec<InventoryItem>, x: u64, y: u64) -> Player {
-        // get user input an assign the input to `name`
-        println!("What's your name?");
-
-        let mut name = String::new();
-
-        io::stdin()
-            .read_line(&mut name)
-            .expect("Could not read line");
-
+    pub fn new(name: String, inventory: Vec<InventoryItem>, x: u64, y: u64) -> Player {
         Player {
             name: name,
             inventory: inventory,
             location: Location { x: x, y: y },
+            health: MAX_HEALTH,
+            max_health: MAX_HEALTH,
+            stats: BTreeMap::new(),
+            flags: BTreeSet::new(),
+            visited: vec![Location { x: x, y: y }].into_iter().collect(),
+            map_revealed: false,
         }
     }
 
-    pub fn print_inventory(&self) {
-        if self.inventory.is_empty() {
-            println!("Oops! You don't have any items. Why not take a look around?");
-        } else {
-            println!("{:?}", self.inventory);
-        }
+    // A new player at the start of `map`, with the health and stats
+    // the world gives them.
+    pub fn starting_in(name: String, map: &Map) -> Player {
+        let mut player = Player::new(name, vec![], map.start.x, map.start.y);
+        player.max_health = map.max_health.unwrap_or(MAX_HEALTH);
+        player.health = player.max_health;
+        player.stats = map.stats.clone();
+        player
     }
 
+    // Items the player already has some of are stacked together.
     pub fn add_to_inventory(&mut self, item: InventoryItem) {
-        println!("{} has been added to your inventory!", item.name);
-        self.inventory.push(item);
+        inventory_item::add(&mut self.inventory, item);
     }
 
     pub fn inventory(&self) -> &[InventoryItem] {
@@ -55,4 +79,16 @@ impl Player {
     pub fn location_mut(&mut self) -> &mut Location {
         &mut self.location
     }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health == 0
+    }
+}
+
+fn default_max_health() -> u64 {
+    MAX_HEALTH
 }
@@ -0,0 +1,186 @@
+use std::collections::BTreeSet;
+use event::Event;
+use map::Map;
+use player::Player;
+
+// Something for the player to do, made of objectives they can do in
+// any order. Every quest in the world is open from the start, and
+// Game::track_quests ticks objectives off as the events that do them
+// happen. Like
+//
+//     "quests": [
+//       {
+//         "name": "Toothy grin",
+//         "description": "Get a crocodile's tooth.",
+//         "objectives": [
+//           { "talk_to": "Crocodile Woman" },
+//           { "collect": { "item": "crocodile tooth" } }
+//         ]
+//       }
+//     ]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Quest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub objectives: Vec<Objective>,
+    // the objectives the player has done, by their place in
+    // `objectives`. Once done they stay done, even if the player drops
+    // what they collected.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub done: BTreeSet<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    // have `count` of an item at once, however the player got them
+    Collect {
+        item: String,
+        #[serde(default = "default_count")]
+        count: u64,
+    },
+    // give (or trade) an item to an NPC
+    Deliver { item: String, to: String },
+    // go to the room with this name
+    Visit(String),
+    TalkTo(String), // the NPC's name
+}
+
+// What the player has to do to win the game. Worlds without one go
+// on until the player quits.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Win {
+    AllQuests,
+    Quest(String), // the name of the quest
+}
+
+impl Quest {
+    pub fn is_complete(&self) -> bool {
+        (0..self.objectives.len()).all(|i| self.done.contains(&i))
+    }
+}
+
+impl Objective {
+    // Whether `event`, which just happened, does this objective.
+    // `player` and `map` are how things are after it happened.
+    pub fn done_by(&self, event: &Event, player: &Player, map: &Map) -> bool {
+        match *self {
+            Objective::Collect { ref item, count } => {
+                let got = match *event {
+                    Event::ItemPickedUp { item: ref got, .. } |
+                    Event::ItemTaken { item: ref got, .. } |
+                    Event::ItemReceived { item: ref got, .. } |
+                    Event::Traded { ref got, .. } => got,
+                    _ => return false,
+                };
+
+                same(got, item) &&
+                player.inventory()
+                    .iter()
+                    .find(|held| held.is_called(item))
+                    .map(|held| held.count() >= count)
+                    .unwrap_or(false)
+            }
+            Objective::Deliver { ref item, ref to } => {
+                match *event {
+                    Event::ItemGiven { item: ref gave, ref npc, .. } |
+                    Event::Traded { ref gave, ref npc, .. } => same(gave, item) && same(npc, to),
+                    _ => false,
+                }
+            }
+            Objective::Visit(ref room) => {
+                match *event {
+                    Event::Moved { ref to, .. } |
+                    Event::Teleported { ref to } => {
+                        map.rooms.get(to).map(|r| same(&r.name, room)).unwrap_or(false)
+                    }
+                    _ => false,
+                }
+            }
+            Objective::TalkTo(ref name) => {
+                match *event {
+                    Event::Talked { ref npc, .. } |
+                    Event::Said { ref npc, .. } => same(npc, name),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+impl Win {
+    pub fn holds(&self, quests: &[Quest]) -> bool {
+        match *self {
+            Win::AllQuests => quests.iter().all(|quest| quest.is_complete()),
+            Win::Quest(ref name) => {
+                quests.iter().any(|quest| quest.name == *name && quest.is_complete())
+            }
+        }
+    }
+}
+
+// Names in world files don't have to match the case the game uses.
+fn same(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+fn default_count() -> u64 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use inventory_item::InventoryItem;
+    use location::Location;
+    use room::Room;
+
+    #[test]
+    fn objectives_are_done_by_the_right_events() {
+        let quest: Quest = serde_json::from_str(r#"{
+            "name": "errands",
+            "objectives": [
+                { "collect": { "item": "coin", "count": 2 } },
+                { "deliver": { "item": "hat", "to": "Pat" } },
+                { "visit": "Kitchen" },
+                { "talk_to": "Pat" }
+            ]
+        }"#)
+            .unwrap();
+        let map = Map::new("Quest Test",
+                           vec![Room::new(0, 0, "kitchen".to_string(), String::new(), vec![],
+                                          vec![])]);
+        let mut player = Player::new("Liz".to_string(), vec![], 0, 0);
+        let done = |event: &Event, player: &Player| -> Vec<bool> {
+            quest.objectives.iter().map(|o| o.done_by(event, player, &map)).collect()
+        };
+
+        let picked_up = Event::ItemPickedUp {
+            item: "coin".to_string(),
+            count: 1,
+        };
+        player.add_to_inventory(InventoryItem::new(1, "coin".to_string(), String::new()));
+        assert_eq!(done(&picked_up, &player), vec![false, false, false, false]);
+        player.add_to_inventory(InventoryItem::new(1, "coin".to_string(), String::new()));
+        assert_eq!(done(&picked_up, &player), vec![true, false, false, false]);
+
+        let given = Event::ItemGiven {
+            item: "hat".to_string(),
+            count: 1,
+            npc: "Pat".to_string(),
+        };
+        assert_eq!(done(&given, &player), vec![false, true, false, false]);
+
+        let moved = Event::Teleported { to: Location::new(0, 0) };
+        assert_eq!(done(&moved, &player), vec![false, false, true, false]);
+
+        let talked = Event::Talked {
+            npc: "Pat".to_string(),
+            dialogue: "hi".to_string(),
+        };
+        assert_eq!(done(&talked, &player), vec![false, false, false, true]);
+    }
+}
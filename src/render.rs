@@ -0,0 +1,401 @@
+use command::{Lockable, ParseError};
+use direction::Direction;
+use event::{Container, Event};
+use inventory_item::InventoryItem;
+use quest::Objective;
+
+// What we ask the player before every command.
+pub const PROMPT: &str = "What would you like to do? (enter 'help' to see a list of commands)";
+
+// What we ask instead while the player is talking to someone.
+pub const TALK_PROMPT: &str = "What do you say? (enter a number, or 'bye' to stop talking)";
+
+// ...and once the player has died.
+pub const GAME_OVER_PROMPT: &str = "Enter 'restart' to play again, 'load _slot_' to load a saved \
+                                    game, or 'quit'.";
+
+// The text for a whole command's worth of events, one after the
+// other.
+pub fn all(events: &[Event]) -> String {
+    events.iter().map(text).collect::<Vec<String>>().join("\n")
+}
+
+// Turn an event into the English text the player sees. Some events
+// take more than one line; the lines are separated by "\n", with no
+// newline at the end.
+//
+// This is the only place in the game that knows how things are worded,
+// so a different frontend (or a translation) can replace it without
+// touching the game logic.
+pub fn text(event: &Event) -> String {
+    match *event {
+        Event::Welcome { ref player, ref title } => format!("Hi {}\nWelcome to {}", player, title),
+        Event::Help => {
+            ["exit (or quit, q): exit the game",
+             "move north, south, east, west, up, down, in, out (or go north, north, n): move in \
+             this direction",
+             "look around (or look, l): see a description of the current room",
+             "pick up (_count_) _item_ (or get, grab): add the item to your inventory",
+             "take (_count_) _item_ (from _npc_): take an item from an NPC",
+             "drop (_count_) _item_: leave an item in the current room",
+             "give (_count_) _item_ to _npc_: give an item to an NPC",
+             "put (_count_) _item_ in _item_: put an item inside something, like a bag",
+             "trade (_count_) _item_ for _item_ (with _npc_): trade with an NPC",
+             "buy _item_ (from _npc_): buy something from an NPC",
+             "use _item_: use an item in your inventory",
+             "unlock _direction_ with _item_: unlock a locked exit",
+             "unlock _item_ with _item_: unlock a locked container, like a chest",
+             "open _item_, close _item_: open or close a container",
+             "look in _item_: see what's inside a container",
+             "talk (to _npc_): talk to an NPC",
+             "attack (_npc_) (with _item_) (or fight, hit, kill): fight an NPC",
+             "display map (or map, m): look at map",
+             "print inventory (or inventory, i): show current player inventory",
+             "status (or stats): see how healthy you are, and your other stats",
+             "quests: see what there is to do",
+             "save _slot_: save your game",
+             "load _slot_: load a saved game",
+             "restart: start the game again from the beginning",
+             "undo: take back your last command (but not saving or loading)",
+             "redo: do what you just undid again"]
+                .join("\n")
+        }
+        Event::Quit => "Thanks for playing!".to_string(),
+        Event::CommandNotUnderstood { ref error } => parse_error(error),
+
+        Event::Moved { dir, .. } => format!("You have moved {}.", dir),
+        Event::BlockedDirection { dir } => {
+            format!("You can not go {}. Try a different direction.", dir)
+        }
+        Event::ExitLocked { dir } => format!("The way {} is locked.", dir),
+        Event::Unlocked { ref what, ref item, consumed } => {
+            if consumed {
+                format!("You unlocked {} with the {}, which is now gone.", lockable(what), item)
+            } else {
+                format!("You unlocked {} with the {}.", lockable(what), item)
+            }
+        }
+        Event::NotLocked { what: Lockable::Exit(dir) } => {
+            format!("There is nothing to unlock to the {}.", dir)
+        }
+        Event::NotLocked { what: Lockable::Item(ref item) } => {
+            format!("The {} isn't locked.", item)
+        }
+        Event::WrongKey { ref what, ref item } => {
+            format!("The {} doesn't unlock {}.", item, lockable(what))
+        }
+
+        Event::ItemPickedUp { ref item, count } |
+        Event::ItemTaken { ref item, count, .. } => {
+            format!("{} has been added to your inventory!", quantity(item, count))
+        }
+        Event::ItemDropped { ref item, count } => {
+            format!("You dropped {}.", quantity(item, count))
+        }
+        Event::ItemGiven { ref item, count, ref npc } => {
+            format!("You gave {} to {}.", quantity(item, count), npc)
+        }
+        Event::ItemPut { ref item, count, ref container } => {
+            format!("You put {} in the {}.", quantity(item, count), container)
+        }
+        Event::ItemUsed { ref effects, .. } => effects.clone(),
+        Event::ItemNotFound { ref name, ref container } => {
+            match *container {
+                Container::Room => format!("Sorry, {} wasn't found in the current room", name),
+                Container::Npc(ref npc) => format!("Sorry, {} doesn't have {}.", npc, name),
+                Container::Player => {
+                    format!("Sorry, you don't have {} in your inventory.", name)
+                }
+                Container::Item(ref item) => {
+                    format!("Sorry, there is no {} in the {}.", name, item)
+                }
+            }
+        }
+        Event::ItemNotNearby { ref name } => {
+            format!("Sorry, there's no {} here or in your inventory.", name)
+        }
+        Event::NotEnough { ref name, ref container, have } => {
+            match *container {
+                Container::Room => format!("Sorry, there are only {} {} here.", have, name),
+                Container::Npc(ref npc) => format!("Sorry, {} only has {} {}.", npc, have, name),
+                Container::Player => format!("Sorry, you only have {} {}.", have, name),
+                Container::Item(ref item) => {
+                    format!("Sorry, there are only {} {} in the {}.", have, name, item)
+                }
+            }
+        }
+        Event::Traded { ref npc, ref gave, gave_count, ref got } => {
+            format!("You gave {} {} and got the {}.", npc, quantity(gave, gave_count), got)
+        }
+        Event::TradeRefused { ref npc, ref item } => {
+            format!("{} won't trade you the {}.", npc, item)
+        }
+        Event::TradeWants { ref npc, ref item, ref wants, price } => {
+            let s = if price == 1 { "" } else { "s" };
+            format!("{} wants {} {}{} for the {}.", npc, price, wants, s, item)
+        }
+        Event::Opened { ref item, ref contents } => {
+            if contents.is_empty() {
+                format!("You open the {}. It's empty.", item)
+            } else {
+                format!("You open the {}. Inside: {}", item, items_text(contents))
+            }
+        }
+        Event::Closed { ref item } => format!("You close the {}.", item),
+        Event::AlreadyOpen { ref item } => format!("The {} is already open.", item),
+        Event::AlreadyClosed { ref item } => format!("The {} is already closed.", item),
+        Event::ContainerLocked { ref item } => format!("The {} is locked.", item),
+        Event::ContainerClosed { ref item } => format!("The {} is closed.", item),
+        Event::NotAContainer { ref name } => format!("You can't put things in the {}.", name),
+        Event::ItemInItself { ref item } => {
+            format!("You can't put the {} inside itself.", item)
+        }
+
+        Event::Healed { health, .. } => format!("You feel better. Your health is now {}.", health),
+        Event::Hurt { amount, health } => {
+            format!("Ouch! You lose {} health. Your health is now {}.", amount, health)
+        }
+        Event::StatChanged { ref stat, by, value } => {
+            if by < 0 {
+                format!("Your {} goes down to {}.", stat, value)
+            } else {
+                format!("Your {} goes up to {}.", stat, value)
+            }
+        }
+        Event::FlagSet { ref flag } => format!("(You are now: {})", flag),
+        Event::Teleported { .. } => "Whoosh! You are somewhere else now.".to_string(),
+        Event::ExitRevealed { dir, .. } => {
+            format!("You hear something shift. A hidden way {} has opened up.", dir)
+        }
+        Event::ItemSpawned { ref item } => format!("A {} appears!", item),
+        Event::ItemReceived { ref item, count } => format!("You got {}!", quantity(item, count)),
+        Event::ExitUnlocked { .. } => "Somewhere, a lock clicks open.".to_string(),
+        Event::MapRevealed => "The whole map is clear to you now.".to_string(),
+        Event::DialogueChanged { ref npc } => {
+            format!("{} seems to have something new to say.", npc)
+        }
+        Event::NothingHappened => "Nothing happens.".to_string(),
+        Event::NpcNotFound { ref name } => format!("Sorry, {} isn't here.", name),
+        Event::NpcArrived { ref npc, dir: Some(dir) } => {
+            format!("{} arrives from the {}.", npc, dir)
+        }
+        Event::NpcArrived { ref npc, dir: None } => format!("{} appears.", npc),
+        Event::NpcLeft { ref npc, dir: Some(dir) } => format!("{} leaves, heading {}.", npc, dir),
+        Event::NpcLeft { ref npc, dir: None } => format!("{} leaves.", npc),
+        Event::NoOneHere => "There's nobody here.".to_string(),
+        Event::WhichNpc { ref npcs } => format!("Who do you mean? {}", npcs.join(", ")),
+
+        Event::Attacked { ref npc, ref with, damage, health } => {
+            let hit = match *with {
+                Some(ref weapon) => {
+                    format!("You hit {} with the {} for {} damage", npc, weapon, damage)
+                }
+                None => format!("You punch {} for {} damage", npc, damage),
+            };
+            if health > 0 {
+                format!("{}. They have {} health left.", hit, health)
+            } else {
+                format!("{}.", hit)
+            }
+        }
+        Event::NpcKilled { ref npc, ref dropped } => {
+            if dropped.is_empty() {
+                format!("{} is dead.", npc)
+            } else {
+                format!("{} is dead. They drop: {}", npc, items_text(dropped))
+            }
+        }
+        Event::NpcAttacked { ref npc, damage, health } => {
+            format!("{} hits you for {} damage. Your health is now {}.", npc, damage, health)
+        }
+        Event::WontFight { ref npc } => format!("{} doesn't want to fight.", npc),
+        Event::NotAWeapon { ref item } => format!("The {} is no use as a weapon.", item),
+        Event::Fled { ref npcs } => format!("You got away from {}!", npcs.join(" and ")),
+
+        Event::LookedAround { ref description,
+                              ref exits,
+                              ref locked,
+                              ref items,
+                              ref npcs } => {
+            // display the current room's description and exits
+            let mut lines = vec![description.clone(), exits_text(exits, locked)];
+            // if the room has any items, display information about them
+            if !items.is_empty() {
+                lines.push(format!("This room contains: {}", items_text(items)));
+            }
+            // display information about the room's NPCs
+            for npc in npcs {
+                lines.push(format!("{} is here too!", npc.name));
+                if !npc.inventory.is_empty() {
+                    lines.push(format!("{} has {}.", npc.name, items_text(&npc.inventory)));
+                }
+            }
+            lines.join("\n")
+        }
+        Event::LookedIn { ref item, ref contents } => {
+            if contents.is_empty() {
+                format!("The {} is empty.", item)
+            } else {
+                format!("The {} contains: {}", item, items_text(contents))
+            }
+        }
+        Event::Talked { ref dialogue, .. } => dialogue.clone(),
+        Event::Said { ref npc, ref line, ref responses } => {
+            let mut lines = vec![format!("{}: {}", npc, line)];
+            for (i, response) in responses.iter().enumerate() {
+                lines.push(format!("  {}. {}", i + 1, response));
+            }
+            lines.join("\n")
+        }
+        Event::ConversationEnded { ref npc } => format!("You stop talking to {}.", npc),
+        Event::NotAResponse { responses } => {
+            format!("Pick a response from 1 to {}, or say 'bye'.", responses)
+        }
+        Event::MapShown { ref map } => map.clone(),
+        Event::InventoryShown { ref items } => {
+            if items.is_empty() {
+                "Oops! You don't have any items. Why not take a look around?".to_string()
+            } else {
+                format!("You have: {}", items_text(items))
+            }
+        }
+
+        Event::StatusShown { health, max_health, ref stats } => {
+            let mut lines = vec![format!("health: {}/{}", health, max_health)];
+            lines.extend(stats.iter().map(|(stat, value)| format!("{}: {}", stat, value)));
+            lines.join("\n")
+        }
+
+        Event::Hazard { ref description } => description.clone(),
+        Event::Died => "You have died. GAME OVER".to_string(),
+        Event::GameIsOver => "You can't do that, you're dead!".to_string(),
+        Event::Restarted => "Starting again from the beginning...".to_string(),
+
+        Event::Undone { ref input } => format!("You take back {:?}.", input),
+        Event::Redone { ref input } => format!("You do {:?} again.", input),
+        Event::NothingToUndo => "There's nothing to undo.".to_string(),
+        Event::NothingToRedo => "There's nothing to redo.".to_string(),
+
+        Event::QuestsShown { ref quests } => {
+            if quests.is_empty() {
+                return "There's nothing in particular to do here. Have fun!".to_string();
+            }
+
+            let mut lines = vec![];
+            for quest in quests {
+                let done = if quest.is_complete() { " (complete)" } else { "" };
+                if quest.description.is_empty() {
+                    lines.push(format!("{}{}", quest.name, done));
+                } else {
+                    lines.push(format!("{}{}: {}", quest.name, done, quest.description));
+                }
+
+                for (i, objective) in quest.objectives.iter().enumerate() {
+                    let tick = if quest.done.contains(&i) { "x" } else { " " };
+                    lines.push(format!("  [{}] {}", tick, objective_text(objective)));
+                }
+            }
+            lines.join("\n")
+        }
+        Event::ObjectiveDone { ref quest, ref objective } => {
+            format!("{}: you did \"{}\".", quest, objective_text(objective))
+        }
+        Event::QuestDone { ref quest } => format!("You completed the quest {:?}!", quest),
+        Event::Won { ref quests, turns } => {
+            let mut lines = vec![format!("You won the game in {} turns!", turns)];
+            if !quests.is_empty() {
+                lines.push(format!("Quests completed: {}", quests.join(", ")));
+            }
+            lines.push("Thanks for playing!".to_string());
+            lines.join("\n")
+        }
+
+        Event::Saved { ref slot } => format!("Your game has been saved to {:?}.", slot),
+        Event::SaveFailed { ref reason, .. } => {
+            format!("Sorry, your game could not be saved: {}", reason)
+        }
+        Event::Loaded { ref slot } => format!("Your game has been loaded from {:?}.", slot),
+        Event::LoadFailed { ref slot, ref reason } => {
+            format!("Sorry, {:?} could not be loaded: {}", slot, reason)
+        }
+    }
+}
+
+fn parse_error(error: &ParseError) -> String {
+    match *error {
+        ParseError::Empty => "Type a command, or 'help' to see a list of commands.".to_string(),
+        ParseError::UnknownVerb { ref verb, suggestion: Some(ref suggestion) } => {
+            format!("No such command: {:?}. Did you mean {:?}?", verb, suggestion)
+        }
+        ParseError::UnknownVerb { ref verb, suggestion: None } => {
+            format!("No such command: {:?}. Sorry!", verb)
+        }
+        ParseError::MissingObject { ref verb } => format!("What do you want to {}?", verb),
+        ParseError::UnexpectedWords { ref verb, ref words } => {
+            format!("Sorry, you can't {} {}.", verb, words)
+        }
+        ParseError::UnknownDirection { .. } => {
+            "That is not a valid direction. Try north, south, east, west, up, down, in, or out."
+                .to_string()
+        }
+    }
+}
+
+// "potion", or "potion x2" when there is more than one.
+fn quantity(name: &str, count: u64) -> String {
+    if count == 1 {
+        name.to_string()
+    } else {
+        format!("{} x{}", name, count)
+    }
+}
+
+// A list of items, with what's inside any open containers in
+// brackets after them, like "bag (potion x2, hat), closed chest".
+fn items_text(items: &[InventoryItem]) -> String {
+    let names: Vec<String> = items.iter()
+        .map(|item| {
+            let name = quantity(&item.name, item.count());
+            match item.contents {
+                Some(_) if item.closed => format!("closed {}", name),
+                Some(ref contents) if contents.is_empty() => format!("{} (empty)", name),
+                Some(ref contents) => format!("{} ({})", name, items_text(contents)),
+                None => name,
+            }
+        })
+        .collect();
+    names.join(", ")
+}
+
+// "collect gold coin x3", or "talk to Prudence".
+fn objective_text(objective: &Objective) -> String {
+    match *objective {
+        Objective::Collect { ref item, count } => format!("collect {}", quantity(item, count)),
+        Objective::Deliver { ref item, ref to } => format!("give the {} to {}", item, to),
+        Objective::Visit(ref room) => format!("visit {}", room),
+        Objective::TalkTo(ref npc) => format!("talk to {}", npc),
+    }
+}
+
+// "the way north", or "the chest".
+fn lockable(what: &Lockable) -> String {
+    match *what {
+        Lockable::Exit(dir) => format!("the way {}", dir),
+        Lockable::Item(ref item) => format!("the {}", item),
+    }
+}
+
+fn exits_text(exits: &[Direction], locked: &[Direction]) -> String {
+    if exits.is_empty() {
+        "There is no way out of here!".to_string()
+    } else {
+        let names: Vec<String> = exits.iter()
+            .map(|dir| if locked.contains(dir) {
+                format!("{} (locked)", dir)
+            } else {
+                dir.to_string()
+            })
+            .collect();
+        format!("Exits: {}.", names.join(", "))
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A small random number generator (xorshift64*), so that NPCs can
+// wander around without the game needing another dependency. Given
+// the same seed it always gives the same numbers, so playing the same
+// commands with the same seed (see `--seed`) plays the same game.
+// That keeps tests predictable, and makes bugs easy to reproduce.
+//
+// It's saved along with the game, so a loaded game carries on with
+// the numbers it would have had.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Rng {
+    seed: u64, // what it started from, for telling other people
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            seed: seed,
+            // xorshift gets stuck at zero, so zero starts somewhere else
+            state: if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed },
+        }
+    }
+
+    // A generator seeded from the clock, for when nobody cares which
+    // numbers come out.
+    pub fn from_time() -> Rng {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Rng::new(now.as_secs() ^ u64::from(now.subsec_nanos()))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // A number from 0 up to (but not including) `n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_gives_the_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let numbers: Vec<u64> = (0..5).map(|_| a.below(10)).collect();
+
+        assert_eq!(numbers, (0..5).map(|_| b.below(10)).collect::<Vec<u64>>());
+        assert!(numbers.iter().all(|&n| n < 10));
+        assert_ne!(Rng::new(0).next_u64(), 0);
+        assert_eq!(a.seed(), 42);
+    }
+}
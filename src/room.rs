@@ -1,15 +1,44 @@
+use std::collections::BTreeMap;
+use direction::Direction;
+use effect::Effect;
+use exit::Exit;
 use location::Location;
 use inventory_item::InventoryItem;
 use npc::NPC;
 
 // We want to be able to debug Room and `==` it.
-#[derive(Debug, Eq, PartialEq)]
+// We also want to be able to read it out of a world file, write it
+// to a save file, and clone it when saving.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Room {
     pub location: Location, // it has a Location
     pub name: String, // it has a name
     pub description: String, // it has a description
+    #[serde(default)]
     pub items: Vec<InventoryItem>, // it has items/inventory (may be empty)
-    pub npc: NPC, // it may have an NPC / non-player character
+    #[serde(default)]
+    pub npcs: Vec<NPC>, // it has NPCs / non-player characters (may be none)
+    // where each of its exits leads. A room that doesn't list any
+    // exits is connected to its neighbours on the grid (see Map::new).
+    // A BTreeMap keeps them in order in save files.
+    #[serde(default)]
+    pub exits: BTreeMap<Direction, Exit>,
+    // something that happens to the player every turn they spend here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hazard: Option<Hazard>,
+}
+
+// Something in a room that hurts the player (or does anything else
+// an item can do), like
+//
+//     "hazard": { "description": "The smoke makes you cough.", "effects": [{ "damage": 5 }] }
+//
+// It happens at the end of every turn the player is in the room.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Hazard {
+    pub description: String,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
 }
 
 impl Room {
@@ -20,7 +49,7 @@ impl Room {
                name: String,
                description: String,
                items: Vec<InventoryItem>,
-               npc: NPC)
+               npcs: Vec<NPC>)
                -> Room {
         // returns a Room
         Room {
@@ -29,7 +58,9 @@ impl Room {
             name: name, // save the name
             description: description, // save the description
             items: items, // save the inventory
-            npc: npc, // save the NPC
+            npcs: npcs, // save the NPCs
+            exits: BTreeMap::new(), // filled in by Map::new
+            hazard: None, // rooms are safe unless the world says otherwise
         }
     }
 
@@ -41,11 +72,19 @@ impl Room {
         &mut self.items
     }
 
-    pub fn npc(&self) -> &NPC {
-        &self.npc
+    // The NPCs who are here right now, leaving out any who are away
+    // (see NPC::schedule).
+    pub fn npcs(&self) -> Vec<&NPC> {
+        self.npcs.iter().filter(|npc| !npc.away).collect()
+    }
+
+    // The NPC called `name`, if they're here. Case doesn't matter,
+    // since the command parser lowercases everything.
+    pub fn npc(&self, name: &str) -> Option<&NPC> {
+        self.npcs.iter().find(|npc| !npc.away && npc.is_called(name))
     }
 
-    pub fn npc_mut(&mut self) -> &mut NPC {
-        &mut self.npc
+    pub fn npc_mut(&mut self, name: &str) -> Option<&mut NPC> {
+        self.npcs.iter_mut().find(|npc| !npc.away && npc.is_called(name))
     }
 }
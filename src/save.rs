@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use serde_json;
+use map::Map;
+use player::Player;
+use rng::Rng;
+
+// Bump this whenever the shape of SaveFile changes, so old saves
+// are rejected with a clear message instead of a confusing parse
+// error (or, worse, loading with missing state).
+pub const SAVE_VERSION: u64 = 13;
+
+// Everything about a game that can change while playing. The map
+// is saved whole, because picking things up and taking them from
+// NPCs changes what is in each room.
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u64,
+    pub player: Player,
+    pub map: Map,
+    pub turn: u64, // see Game::tick
+    pub rng: Rng,
+}
+
+impl SaveFile {
+    pub fn new(player: Player, map: Map, turn: u64, rng: Rng) -> SaveFile {
+        SaveFile {
+            version: SAVE_VERSION,
+            player: player,
+            map: map,
+            turn: turn,
+            rng: rng,
+        }
+    }
+}
+
+// Everything that can go wrong when saving or loading.
+#[derive(Debug)]
+pub enum SaveError {
+    BadSlot(String),
+    Io(io::Error),
+    Parse(serde_json::Error),
+    WrongVersion(u64),
+    PlayerOutsideMap,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            SaveError::BadSlot(ref slot) => {
+                write!(f,
+                       "{:?} is not a valid save slot. Use letters, numbers, - and _.",
+                       slot)
+            }
+            SaveError::Io(ref err) => write!(f, "could not access save file: {}", err),
+            SaveError::Parse(ref err) => write!(f, "could not read save file: {}", err),
+            SaveError::WrongVersion(version) => {
+                write!(f,
+                       "save file is version {}, but this game only knows version {}",
+                       version,
+                       SAVE_VERSION)
+            }
+            SaveError::PlayerOutsideMap => write!(f, "save file puts the player outside the map"),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> SaveError {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> SaveError {
+        SaveError::Parse(err)
+    }
+}
+
+// Turn a slot name like "before-boss" into a file in `dir`. Slots
+// are restricted to a safe set of characters so that a slot can't
+// be used to write somewhere outside of `dir`.
+pub fn slot_path(dir: &Path, slot: &str) -> Result<PathBuf, SaveError> {
+    let valid = !slot.is_empty() &&
+                slot.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(dir.join(format!("{}.json", slot)))
+    } else {
+        Err(SaveError::BadSlot(slot.to_string()))
+    }
+}
+
+pub fn save(path: &Path, save: &SaveFile) -> Result<(), SaveError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let json = serde_json::to_string_pretty(save)?;
+    File::create(path)?.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<SaveFile, SaveError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+
+    // Look at the version before anything else, since the rest of
+    // the file might not look like a SaveFile at all.
+    let value: serde_json::Value = serde_json::from_str(&contents)?;
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+
+    if version != SAVE_VERSION {
+        return Err(SaveError::WrongVersion(version));
+    }
+
+    let save: SaveFile = serde_json::from_value(value)?;
+
+    if !save.map.rooms.contains_key(&save.player.location) {
+        return Err(SaveError::PlayerOutsideMap);
+    }
+
+    Ok(save)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use inventory_item::InventoryItem;
+    use npc::NPC;
+    use room::Room;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("adventure-game-{}-{}", name, process::id()))
+    }
+
+    fn map() -> Map {
+        let potion = InventoryItem::new(1, "potion".to_string(), "yum".to_string());
+        let npc = NPC::new("Pat".to_string(), vec![], "hi".to_string());
+        Map::new("Save Test",
+                 vec![Room::new(0, 0, "a".to_string(), String::new(), vec![potion], vec![npc])])
+    }
+
+    #[test]
+    fn round_trips_the_game_state() {
+        let dir = temp_dir("round-trip");
+        let path = slot_path(&dir, "slot1").unwrap();
+
+        let mut map = map();
+        let mut player = Player::new("Liz".to_string(), vec![], 0, 0);
+        let potion = map.rooms.values_mut().next().unwrap().items.remove(0);
+        player.add_to_inventory(potion);
+
+        let mut rng = Rng::new(3);
+        rng.next_u64();
+        save(&path, &SaveFile::new(player.clone(), map.clone(), 7, rng.clone())).unwrap();
+        let loaded = load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.player, player);
+        assert_eq!(loaded.map, map);
+        assert_eq!(loaded.turn, 7);
+        assert_eq!(loaded.rng, rng);
+    }
+
+    #[test]
+    fn saves_the_same_map_the_same_way() {
+        let grid = || {
+            let rooms = (0..9)
+                .map(|i| Room::new(i % 3, i / 3, i.to_string(), String::new(), vec![], vec![]))
+                .collect();
+            serde_json::to_string(&Map::new("Save Test", rooms)).unwrap()
+        };
+
+        assert_eq!(grid(), grid());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let dir = temp_dir("version");
+        let path = slot_path(&dir, "old").unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        File::create(&path).unwrap().write_all(b"{\"version\": 0}").unwrap();
+
+        let result = load(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        match result {
+            Err(SaveError::WrongVersion(0)) => {}
+            other => panic!("expected a version error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_slots_that_are_paths() {
+        assert!(slot_path(Path::new("saves"), "../oops").is_err());
+        assert!(slot_path(Path::new("saves"), "").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::path::Path;
+use std::process;
+use serde::Serialize;
+use serde_json;
+use game::Game;
+use render;
+
+// A record of a game: everything the player typed, and everything the
+// game said back. `--record <file>` writes one, and `--replay <file>`
+// plays it again to check the game still does the same thing.
+//
+// The file has one JSON object per line. The first line is a Header,
+// and each line after it is a Step, written as soon as it happens so
+// that the transcript survives the game crashing.
+
+// What it takes to start the same game again.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub seed: u64, // see Rng
+    // the world file, or None for the bundled world
+    pub world: Option<String>,
+    pub title: String, // the world's title, to check it's the same world
+    pub player: String, // the player's name
+}
+
+// One line the player typed, and what the game said back.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Step {
+    pub input: String,
+    pub output: String,
+}
+
+// The first step where a replay didn't say what the recording did.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Divergence {
+    pub step: usize, // counting from 1
+    pub input: String,
+    pub expected: String,
+    pub got: String,
+}
+
+// Write a header or a step as one line of `out`.
+pub fn write_line<W: Write, T: Serialize>(out: &mut W, line: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    writeln!(out)?;
+    out.flush()
+}
+
+pub fn read(path: &Path) -> io::Result<(Header, Vec<Step>)> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    let header = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "the transcript is empty")),
+    };
+
+    let mut steps = vec![];
+    for line in lines {
+        steps.push(serde_json::from_str(&line?)?);
+    }
+
+    Ok((header, steps))
+}
+
+// Run each step's input through `game` and check it says the same
+// thing it did when it was recorded. Returns how many steps were
+// replayed, or the first one that came out differently.
+//
+// The game saves to an empty directory of its own while replaying,
+// so that `save` doesn't overwrite the player's real saves and `load`
+// only finds what was saved earlier in the transcript.
+pub fn replay<R: BufRead, W: Write>(game: &mut Game<R, W>,
+                                    steps: &[Step])
+                                    -> Result<usize, Divergence> {
+    let save_dir = env::temp_dir().join(format!("adventure-game-replay-{}", process::id()));
+    let _ = fs::remove_dir_all(&save_dir);
+    let real_save_dir = mem::replace(&mut game.save_dir, save_dir.clone());
+
+    let mut result = Ok(steps.len());
+    for (i, step) in steps.iter().enumerate() {
+        let got = render::all(&game.execute(&step.input));
+
+        if got != step.output {
+            result = Err(Divergence {
+                step: i + 1,
+                input: step.input.clone(),
+                expected: step.output.clone(),
+                got: got,
+            });
+            break;
+        }
+    }
+
+    game.save_dir = real_save_dir;
+    let _ = fs::remove_dir_all(&save_dir);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inventory_item::InventoryItem;
+    use map::Map;
+    use player::Player;
+    use room::Room;
+
+    fn game() -> Game<&'static [u8], Vec<u8>> {
+        let potion = InventoryItem::new(1, "potion".to_string(), "yum".to_string());
+        let map = Map::new("Replay Test",
+                           vec![Room::new(0, 0, "a".to_string(), String::new(), vec![potion],
+                                          vec![]),
+                                Room::new(1, 0, "b".to_string(), String::new(), vec![], vec![])]);
+        let player = Player::new("Liz".to_string(), vec![], 0, 0);
+        Game::new(player, map, true, &b""[..], vec![])
+    }
+
+    fn step(input: &str, output: &str) -> Step {
+        Step {
+            input: input.to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    #[test]
+    fn replays_until_the_output_changes() {
+        let steps = vec![step("pick up potion", "potion has been added to your inventory!"),
+                         step("e", "You have moved east."),
+                         step("w", "You have moved north.")];
+
+        assert_eq!(replay(&mut game(), &steps[..2]), Ok(2));
+        assert_eq!(replay(&mut game(), &steps),
+                   Err(Divergence {
+                       step: 3,
+                       input: "w".to_string(),
+                       expected: "You have moved north.".to_string(),
+                       got: "You have moved west.".to_string(),
+                   }));
+    }
+
+    #[test]
+    fn saves_somewhere_else_while_replaying() {
+        let mut game = game();
+        let saves = env::temp_dir().join(format!("adventure-game-real-saves-{}", process::id()));
+        game.save_dir = saves.clone();
+        let steps = vec![step("save here", "Your game has been saved to \"here\"."),
+                         step("load here", "Your game has been loaded from \"here\".")];
+
+        assert_eq!(replay(&mut game, &steps), Ok(2));
+        // and the save is gone afterwards, so it can't leak into
+        // the next replay
+        assert!(replay(&mut game, &steps[1..]).is_err());
+        assert!(!saves.exists());
+        assert_eq!(game.save_dir, saves);
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let header = Header {
+            seed: 9,
+            world: None,
+            title: "Replay Test".to_string(),
+            player: "Liz".to_string(),
+        };
+        let steps = vec![step("e", "You have moved east.")];
+        let path = env::temp_dir().join(format!("adventure-game-transcript-{}", process::id()));
+
+        {
+            let mut file = File::create(&path).unwrap();
+            write_line(&mut file, &header).unwrap();
+            write_line(&mut file, &steps[0]).unwrap();
+        }
+        let read = read(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.unwrap(), (header, steps));
+    }
+}
@@ -0,0 +1,662 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use dialogue::DialogueTree;
+use direction::Direction;
+use effect::Effect;
+use inventory_item::InventoryItem;
+use location::Location;
+use map::Map;
+use npc::Behaviour;
+use quest::{Objective, Win};
+
+// How bad a problem is. Errors make the game crash or misbehave,
+// so we refuse to play a world that has any. Warnings are probably
+// mistakes, but the game still works.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// The different things that can be wrong with a map.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Problem {
+    // two rooms were given the same Location, so one of them
+    // (`replaced`) was thrown away
+    DuplicateLocation { replaced: String },
+    // there is no room at this Location, but it is inside the grid.
+    // Worlds don't have to fill their grid, but it might be a mistake.
+    MissingRoom,
+    // an exit leads to a Location that has no room
+    ExitToNowhere { dir: Direction, to: Location },
+    // an exit is locked with a key that isn't anywhere in the map
+    MissingKey { dir: Direction, key: String },
+    // the same, for a locked container
+    MissingContainerKey { container: String, key: String },
+    // the player can never walk to this room from the start
+    Unreachable,
+    // the player starts somewhere that has no room
+    StartOutsideMap,
+    // a room or NPC has more than one item with the same name
+    DuplicateItem { item: String, container: String },
+    // an NPC has no name, so the player can't talk about them
+    EmptyNpcName,
+    // an NPC's dialogue tree starts at or leads to a node it
+    // doesn't have
+    MissingDialogueNode { npc: String, node: String },
+    // an NPC's patrol route goes somewhere that has no room
+    PatrolOutsideMap { npc: String, to: Location },
+    // an NPC wants the world's currency for something, but the world
+    // doesn't have one
+    NoCurrency { npc: String, item: String },
+    // a quest objective names a room or NPC that isn't in the world,
+    // so the quest can't be finished
+    NoSuchRoom { quest: String, room: String },
+    NoSuchNpc { quest: String, npc: String },
+    // the world is won by finishing a quest it doesn't have
+    NoSuchQuest { quest: String },
+}
+
+// A single problem, along with where in the map it happened.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub problem: Problem,
+    pub room: Option<String>, // the room's name, if there is a room
+    pub location: Location,
+}
+
+impl Diagnostic {
+    fn new(problem: Problem, room: Option<&str>, location: &Location) -> Diagnostic {
+        Diagnostic {
+            problem: problem,
+            room: room.map(|name| name.to_string()),
+            location: location.clone(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.problem {
+            Problem::DuplicateLocation { .. } |
+            Problem::ExitToNowhere { .. } |
+            Problem::StartOutsideMap |
+            Problem::MissingDialogueNode { .. } |
+            Problem::NoCurrency { .. } => Severity::Error,
+            Problem::MissingRoom |
+            Problem::Unreachable |
+            Problem::MissingKey { .. } |
+            Problem::MissingContainerKey { .. } |
+            Problem::DuplicateItem { .. } |
+            Problem::EmptyNpcName |
+            Problem::PatrolOutsideMap { .. } |
+            Problem::NoSuchRoom { .. } |
+            Problem::NoSuchNpc { .. } |
+            Problem::NoSuchQuest { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.severity() {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+
+        match self.room {
+            Some(ref name) => write!(f, "{:?} ", name)?,
+            None => write!(f, "nothing ")?,
+        }
+
+        write!(f, "at {}, {}: ", self.location.x, self.location.y)?;
+
+        match self.problem {
+            Problem::DuplicateLocation { ref replaced } => {
+                write!(f, "shares its location with {:?}, which was dropped", replaced)
+            }
+            Problem::MissingRoom => write!(f, "there is a hole in the map here"),
+            Problem::ExitToNowhere { dir, ref to } => {
+                write!(f, "the {} exit leads to {}, {}, where there is no room", dir, to.x, to.y)
+            }
+            Problem::Unreachable => write!(f, "can't be reached from the start"),
+            Problem::MissingKey { dir, ref key } => {
+                write!(f, "the {} exit needs {:?}, which isn't anywhere in the map", dir, key)
+            }
+            Problem::MissingContainerKey { ref container, ref key } => {
+                write!(f, "{:?} needs {:?}, which isn't anywhere in the map", container, key)
+            }
+            Problem::StartOutsideMap => write!(f, "the player starts outside the map"),
+            Problem::DuplicateItem { ref item, ref container } => {
+                write!(f, "{} has more than one {:?}", container, item)
+            }
+            Problem::EmptyNpcName => write!(f, "an NPC has no name"),
+            Problem::MissingDialogueNode { ref npc, ref node } => {
+                write!(f, "{:?} has no dialogue node called {:?}", npc, node)
+            }
+            Problem::PatrolOutsideMap { ref npc, ref to } => {
+                write!(f, "{:?} patrols to {}, {}, where there is no room", npc, to.x, to.y)
+            }
+            Problem::NoCurrency { ref npc, ref item } => {
+                write!(f,
+                       "{:?} wants money for {:?}, but the world has no currency",
+                       npc,
+                       item)
+            }
+            Problem::NoSuchRoom { ref quest, ref room } => {
+                write!(f,
+                       "the quest {:?} needs a room called {:?}, which doesn't exist",
+                       quest,
+                       room)
+            }
+            Problem::NoSuchNpc { ref quest, ref npc } => {
+                write!(f,
+                       "the quest {:?} needs an NPC called {:?}, who doesn't exist",
+                       quest,
+                       npc)
+            }
+            Problem::NoSuchQuest { ref quest } => {
+                write!(f, "the game is won by finishing {:?}, which isn't a quest", quest)
+            }
+        }
+    }
+}
+
+pub fn validate(map: &Map) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for room in map.duplicates() {
+        let kept = &map.rooms[&room.location];
+        diagnostics.push(Diagnostic::new(Problem::DuplicateLocation {
+                                             replaced: room.name.clone(),
+                                         },
+                                         Some(&kept.name),
+                                         &room.location));
+    }
+
+    // every spot on the grid between 0, 0 and max_x, max_y should
+    // have a room in it, or at least it usually does
+    for x in 0..map.max_x() + 1 {
+        for y in 0..map.max_y() + 1 {
+            let location = Location::new(x, y);
+            if !map.rooms.contains_key(&location) {
+                diagnostics.push(Diagnostic::new(Problem::MissingRoom, None, &location));
+            }
+        }
+    }
+
+    // Maps don't have to fill their whole grid, but every exit has
+    // to lead somewhere.
+    for room in map.rooms.values() {
+        for (&dir, exit) in &room.exits {
+            if !map.rooms.contains_key(&exit.to) {
+                diagnostics.push(Diagnostic::new(Problem::ExitToNowhere {
+                                                     dir: dir,
+                                                     to: exit.to.clone(),
+                                                 },
+                                                 Some(&room.name),
+                                                 &room.location));
+            }
+
+            // a lock whose key is nowhere in the world can never be
+            // opened
+            if let Some(ref lock) = exit.lock {
+                if !item_exists(map, &lock.key) {
+                    diagnostics.push(Diagnostic::new(Problem::MissingKey {
+                                                         dir: dir,
+                                                         key: lock.key.clone(),
+                                                     },
+                                                     Some(&room.name),
+                                                     &room.location));
+                }
+            }
+        }
+    }
+
+    if map.rooms.contains_key(&map.start) {
+        let reachable = reachable_from(map, &map.start);
+
+        for room in map.rooms.values() {
+            if !reachable.contains(&room.location) {
+                diagnostics.push(Diagnostic::new(Problem::Unreachable,
+                                                 Some(&room.name),
+                                                 &room.location));
+            }
+        }
+    } else {
+        diagnostics.push(Diagnostic::new(Problem::StartOutsideMap, None, &map.start));
+    }
+
+    for room in map.rooms.values() {
+        let npc_items = room.npcs.iter().flat_map(|npc| npc.inventory.iter());
+        for item in room.items.iter().chain(npc_items).flat_map(with_contents) {
+            if let Some(ref lock) = item.lock {
+                if !item_exists(map, &lock.key) {
+                    diagnostics.push(Diagnostic::new(Problem::MissingContainerKey {
+                                                         container: item.name.clone(),
+                                                         key: lock.key.clone(),
+                                                     },
+                                                     Some(&room.name),
+                                                     &room.location));
+                }
+            }
+        }
+
+        for item in duplicate_items(&room.items) {
+            diagnostics.push(Diagnostic::new(Problem::DuplicateItem {
+                                                 item: item,
+                                                 container: "the room".to_string(),
+                                             },
+                                             Some(&room.name),
+                                             &room.location));
+        }
+
+        for npc in &room.npcs {
+            if npc.name.trim().is_empty() {
+                diagnostics.push(Diagnostic::new(Problem::EmptyNpcName,
+                                                 Some(&room.name),
+                                                 &room.location));
+            }
+
+            for item in duplicate_items(&npc.inventory) {
+                diagnostics.push(Diagnostic::new(Problem::DuplicateItem {
+                                                     item: item,
+                                                     container: format!("{:?}", npc.name),
+                                                 },
+                                                 Some(&room.name),
+                                                 &room.location));
+            }
+
+            if let Behaviour::Patrol(ref route) = npc.behaviour {
+                for to in route.iter().filter(|to| !map.rooms.contains_key(to)) {
+                    diagnostics.push(Diagnostic::new(Problem::PatrolOutsideMap {
+                                                         npc: npc.name.clone(),
+                                                         to: to.clone(),
+                                                     },
+                                                     Some(&room.name),
+                                                     &room.location));
+                }
+            }
+
+            for trade in &npc.trades {
+                if trade.wants.is_none() && map.currency.is_none() {
+                    diagnostics.push(Diagnostic::new(Problem::NoCurrency {
+                                                         npc: npc.name.clone(),
+                                                         item: trade.gives.clone(),
+                                                     },
+                                                     Some(&room.name),
+                                                     &room.location));
+                }
+            }
+
+            for node in npc.tree.iter().flat_map(missing_dialogue_nodes) {
+                diagnostics.push(Diagnostic::new(Problem::MissingDialogueNode {
+                                                     npc: npc.name.clone(),
+                                                     node: node,
+                                                 },
+                                                 Some(&room.name),
+                                                 &room.location));
+            }
+        }
+    }
+
+    for quest in &map.quests {
+        for objective in &quest.objectives {
+            let problem = match *objective {
+                Objective::Visit(ref room) if !room_exists(map, room) => {
+                    Problem::NoSuchRoom {
+                        quest: quest.name.clone(),
+                        room: room.clone(),
+                    }
+                }
+                Objective::Deliver { to: ref npc, .. } |
+                Objective::TalkTo(ref npc) if !npc_exists(map, npc) => {
+                    Problem::NoSuchNpc {
+                        quest: quest.name.clone(),
+                        npc: npc.clone(),
+                    }
+                }
+                _ => continue,
+            };
+            diagnostics.push(Diagnostic::new(problem, None, &map.start));
+        }
+    }
+
+    if let Some(Win::Quest(ref name)) = map.win {
+        if !map.quests.iter().any(|quest| quest.name == *name) {
+            diagnostics.push(Diagnostic::new(Problem::NoSuchQuest { quest: name.clone() },
+                                             None,
+                                             &map.start));
+        }
+    }
+
+    // HashMap iteration order is random, so sort the diagnostics to
+    // report them in the same order every time.
+    diagnostics.sort_by_key(|d| d.location.clone());
+    diagnostics
+}
+
+// Walk the map from `start`, the same way a player would, and
+// collect every Location we can get to.
+fn reachable_from(map: &Map, start: &Location) -> HashSet<Location> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    seen.insert(start.clone());
+    queue.push_back(start.clone());
+
+    while let Some(l) = queue.pop_front() {
+        // Locked and hidden exits count, since the player might find
+        // the key or whatever reveals them.
+        for exit in map.rooms[&l].exits.values() {
+            // an exit to nowhere is not somewhere you can stand
+            if map.rooms.contains_key(&exit.to) && seen.insert(exit.to.clone()) {
+                queue.push_back(exit.to.clone());
+            }
+        }
+    }
+
+    seen
+}
+
+// Whether an item called `name` is anywhere in the map, either in a
+// room or with an NPC, or inside something that is, or whether an
+// effect can make one.
+fn item_exists(map: &Map, name: &str) -> bool {
+    let found = map.rooms.values().any(|room| {
+        room.items
+            .iter()
+            .chain(room.npcs.iter().flat_map(|npc| npc.inventory.iter()))
+            .flat_map(with_contents)
+            .any(|item| item.name == name)
+    });
+
+    found ||
+    granted_items(map)
+        .into_iter()
+        .flat_map(with_contents)
+        .any(|item| item.name == name)
+}
+
+// The items effects can make out of thin air, like with
+// `spawn_item` or `give_item`, wherever those effects are in the
+// map.
+fn granted_items(map: &Map) -> Vec<&InventoryItem> {
+    let mut effects = vec![];
+    for room in map.rooms.values() {
+        let npc_items = room.npcs.iter().flat_map(|npc| npc.inventory.iter());
+        for item in room.items.iter().chain(npc_items).flat_map(with_contents) {
+            effects.extend(item.actions.iter());
+        }
+        effects.extend(room.hazard.iter().flat_map(|hazard| hazard.effects.iter()));
+
+        let responses = room.npcs
+            .iter()
+            .flat_map(|npc| npc.tree.iter())
+            .flat_map(|tree| tree.nodes.values())
+            .flat_map(|node| node.responses.iter());
+        for response in responses {
+            effects.extend(response.effects.iter());
+        }
+    }
+
+    let mut items = vec![];
+    while let Some(effect) = effects.pop() {
+        match *effect {
+            Effect::SpawnItem(ref item) | Effect::GiveItem(ref item) => items.push(item),
+            // any one of these might happen
+            Effect::OneOf(ref others) => effects.extend(others.iter()),
+            _ => {}
+        }
+    }
+    items
+}
+
+// `item`, and everything inside it, and everything inside that.
+fn with_contents(item: &InventoryItem) -> Vec<&InventoryItem> {
+    let mut items = vec![item];
+    for inside in item.contents.iter().flat_map(|contents| contents.iter()) {
+        items.extend(with_contents(inside));
+    }
+    items
+}
+
+fn room_exists(map: &Map, name: &str) -> bool {
+    map.rooms.values().any(|room| room.name.to_lowercase() == name.to_lowercase())
+}
+
+fn npc_exists(map: &Map, name: &str) -> bool {
+    map.rooms.values().flat_map(|room| room.npcs.iter()).any(|npc| npc.is_called(name))
+}
+
+// The nodes a dialogue tree starts at or leads to, but doesn't have.
+fn missing_dialogue_nodes(tree: &DialogueTree) -> Vec<String> {
+    let gotos = tree.nodes
+        .values()
+        .flat_map(|node| node.responses.iter())
+        .filter_map(|response| response.goto.as_ref());
+    let mut missing = vec![];
+
+    for node in Some(&tree.start).into_iter().chain(gotos) {
+        if !tree.nodes.contains_key(node) && !missing.contains(node) {
+            missing.push(node.clone());
+        }
+    }
+
+    missing
+}
+
+// The names of items that appear more than once in a list. There
+// can be more than one of the same container, since they don't
+// stack (see InventoryItem::stacks_with).
+fn duplicate_items(items: &[InventoryItem]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut duplicates = vec![];
+
+    for item in items.iter().filter(|item| item.contents.is_none()) {
+        if !seen.insert(&item.name) && !duplicates.contains(&item.name) {
+            duplicates.push(item.name.clone());
+        }
+    }
+
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exit::Exit;
+    use npc::NPC;
+    use room::Room;
+    use serde_json;
+
+    fn room(x: u64, y: u64, name: &str, items: Vec<InventoryItem>) -> Room {
+        Room::new(x,
+                  y,
+                  name.to_string(),
+                  String::new(),
+                  items,
+                  vec![NPC::new(format!("{} NPC", name), vec![], String::new())])
+    }
+
+    fn item(name: &str) -> InventoryItem {
+        InventoryItem::new(1, name.to_string(), String::new())
+    }
+
+    fn problems(map: &Map) -> Vec<Problem> {
+        map.validate().into_iter().map(|d| d.problem).collect()
+    }
+
+    #[test]
+    fn a_full_grid_has_no_problems() {
+        let map = Map::new("ok",
+                           vec![room(0, 0, "a", vec![]),
+                                room(1, 0, "b", vec![]),
+                                room(0, 1, "c", vec![]),
+                                room(1, 1, "d", vec![])]);
+
+        assert_eq!(map.validate(), vec![]);
+    }
+
+    #[test]
+    fn reports_duplicate_locations() {
+        let map = Map::new("dupes",
+                           vec![room(0, 0, "first", vec![]), room(0, 0, "second", vec![])]);
+
+        assert_eq!(map.validate(),
+                   vec![Diagnostic {
+                            problem: Problem::DuplicateLocation { replaced: "first".to_string() },
+                            room: Some("second".to_string()),
+                            location: Location::new(0, 0),
+                        }]);
+    }
+
+    #[test]
+    fn reports_rooms_cut_off_by_holes() {
+        let map = Map::new("holey", vec![room(0, 0, "a", vec![]), room(2, 0, "c", vec![])]);
+        let diagnostics = map.validate();
+
+        assert_eq!(problems(&map), vec![Problem::MissingRoom, Problem::Unreachable]);
+        assert_eq!(diagnostics[0].location, Location::new(1, 0));
+        assert_eq!(diagnostics[0].severity(), Severity::Warning);
+        assert_eq!(diagnostics[1].room, Some("c".to_string()));
+    }
+
+    #[test]
+    fn reports_exits_to_nowhere() {
+        let mut a = room(0, 0, "a", vec![]);
+        a.exits.insert(Direction::Up, Exit::new(Location::new(0, 5)));
+        let map = Map::new("stairs", vec![a]);
+
+        assert_eq!(problems(&map),
+                   vec![Problem::ExitToNowhere {
+                            dir: Direction::Up,
+                            to: Location::new(0, 5),
+                        }]);
+    }
+
+    #[test]
+    fn reports_locks_without_keys() {
+        let mut a = room(0, 0, "a", vec![item("brass key")]);
+        a.exits.insert(Direction::North, Exit::locked(Location::new(0, 1), "brass key"));
+        let mut b = room(0, 1, "b", vec![]);
+        b.exits.insert(Direction::South, Exit::locked(Location::new(0, 0), "iron key"));
+        let map = Map::new("locked", vec![a, b]);
+
+        assert_eq!(problems(&map),
+                   vec![Problem::MissingKey {
+                            dir: Direction::South,
+                            key: "iron key".to_string(),
+                        }]);
+    }
+
+    #[test]
+    fn counts_keys_that_effects_make() {
+        let mut wand = item("wand");
+        wand.actions = vec![Effect::OneOf(vec![Effect::SpawnItem(item("brass key")),
+                                               Effect::Heal(1)])];
+        let mut a = room(0, 0, "a", vec![wand]);
+        a.exits.insert(Direction::North, Exit::locked(Location::new(0, 1), "brass key"));
+        let map = Map::new("locked", vec![a, room(0, 1, "b", vec![])]);
+
+        assert_eq!(problems(&map), vec![]);
+
+        // and ones NPCs hand over in conversation
+        let mut a = room(0, 0, "a", vec![]);
+        a.npcs[0].tree = Some(serde_json::from_str(r#"{
+            "start": "hi",
+            "nodes": {
+                "hi": {
+                    "line": "hi",
+                    "responses": [{
+                        "text": "key?",
+                        "effects": [{ "give_item": { "name": "iron key", "effects": "" } }]
+                    }]
+                }
+            }
+        }"#)
+            .unwrap());
+        a.exits.insert(Direction::North, Exit::locked(Location::new(0, 1), "iron key"));
+        let map = Map::new("locked", vec![a, room(0, 1, "b", vec![])]);
+
+        assert_eq!(problems(&map), vec![]);
+    }
+
+    #[test]
+    fn looks_for_keys_inside_containers() {
+        let mut chest = InventoryItem::container("chest".to_string(), String::new());
+        chest.contents = Some(vec![item("brass key")]);
+        let mut safe = InventoryItem::container("safe".to_string(), String::new());
+        safe.lock = Exit::locked(Location::new(0, 0), "iron key").lock;
+        let mut a = room(0, 0, "a", vec![chest, safe]);
+        a.exits.insert(Direction::North, Exit::locked(Location::new(0, 1), "brass key"));
+        let map = Map::new("locked", vec![a, room(0, 1, "b", vec![])]);
+
+        assert_eq!(problems(&map),
+                   vec![Problem::MissingContainerKey {
+                            container: "safe".to_string(),
+                            key: "iron key".to_string(),
+                        }]);
+    }
+
+    #[test]
+    fn reports_a_start_outside_the_map() {
+        let mut map = Map::new("lost", vec![room(0, 0, "a", vec![])]);
+        map.start = Location::new(3, 3);
+
+        assert_eq!(problems(&map), vec![Problem::StartOutsideMap]);
+    }
+
+    #[test]
+    fn reports_duplicate_items_and_nameless_npcs() {
+        let mut a = room(0, 0, "a", vec![item("rock"), item("rock")]);
+        a.npcs[0].name = String::new();
+        let map = Map::new("messy", vec![a]);
+
+        assert_eq!(problems(&map),
+                   vec![Problem::DuplicateItem {
+                            item: "rock".to_string(),
+                            container: "the room".to_string(),
+                        },
+                        Problem::EmptyNpcName]);
+    }
+
+    #[test]
+    fn reports_missing_dialogue_nodes() {
+        let mut a = room(0, 0, "a", vec![]);
+        a.npcs[0].tree = Some(serde_json::from_str(r#"{
+            "start": "hi",
+            "nodes": {
+                "hi": { "line": "hi", "responses": [{ "text": "?", "goto": "huh" }] }
+            }
+        }"#)
+            .unwrap());
+        let map = Map::new("chatty", vec![a]);
+
+        assert_eq!(problems(&map),
+                   vec![Problem::MissingDialogueNode {
+                            npc: "a NPC".to_string(),
+                            node: "huh".to_string(),
+                        }]);
+    }
+
+    #[test]
+    fn reports_quests_that_cant_be_finished() {
+        let mut map = Map::new("questy", vec![room(0, 0, "a", vec![])]);
+        map.quests = vec![serde_json::from_str(r#"{
+            "name": "errands",
+            "objectives": [{ "visit": "A" }, { "visit": "b" }, { "talk_to": "nobody" }]
+        }"#)
+                              .unwrap()];
+        map.win = Some(Win::Quest("chores".to_string()));
+
+        assert_eq!(problems(&map),
+                   vec![Problem::NoSuchRoom {
+                            quest: "errands".to_string(),
+                            room: "b".to_string(),
+                        },
+                        Problem::NoSuchNpc {
+                            quest: "errands".to_string(),
+                            npc: "nobody".to_string(),
+                        },
+                        Problem::NoSuchQuest { quest: "chores".to_string() }]);
+    }
+}
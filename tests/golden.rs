@@ -0,0 +1,116 @@
+// Golden transcript tests: each file in tests/golden is a scripted
+// session played against tests/fixtures/world.json. Lines starting
+// with "> " are what the player types, and everything after one, up
+// to the next, is what the game should say back, like
+//
+//     > pick up lamp
+//     lamp has been added to your inventory!
+//
+// The game is run for real (as the compiled binary) with `--record`,
+// and the transcript it records is compared against the file.
+//
+// When the game is supposed to say something different, regenerate
+// the files from what it says now with
+//
+//     UPDATE_GOLDEN=1 cargo test --test golden
+//
+// and check the diff before committing it.
+
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+fn path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+// The commands in a golden file.
+fn commands(golden: &str) -> Vec<String> {
+    golden.lines()
+        .filter(|line| line.starts_with("> "))
+        .map(|line| line[2..].to_string())
+        .collect()
+}
+
+// Play `commands` and write out what happened in the golden format.
+fn play(name: &str, commands: &[String]) -> String {
+    let transcript = env::temp_dir()
+        .join(format!("adventure-game-golden-{}-{}.jsonl", name, process::id()));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_adventure_game"))
+        .arg("--world")
+        .arg(path("tests/fixtures/world.json"))
+        .args(["--seed", "1", "--record"])
+        .arg(&transcript)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("could not run the game");
+
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        writeln!(stdin, "Tester").unwrap();
+        for command in commands {
+            writeln!(stdin, "{}", command).unwrap();
+        }
+    }
+    assert!(child.wait().unwrap().success(), "the game crashed playing {}", name);
+
+    let recorded = fs::read_to_string(&transcript).unwrap();
+    fs::remove_file(&transcript).unwrap();
+
+    let mut golden = String::new();
+    // the first line is the header, and the rest are steps
+    for line in recorded.lines().skip(1) {
+        let step: serde_json::Value = serde_json::from_str(line).unwrap();
+        golden.push_str(&format!("> {}\n", step["input"].as_str().unwrap()));
+
+        let output = step["output"].as_str().unwrap();
+        if !output.is_empty() {
+            golden.push_str(output);
+            golden.push('\n');
+        }
+    }
+    golden
+}
+
+fn check(name: &str) {
+    let file = path(&format!("tests/golden/{}.txt", name));
+    let expected = fs::read_to_string(&file).expect("could not read golden file");
+    let actual = play(name, &commands(&expected));
+
+    if env::var("UPDATE_GOLDEN").is_ok() {
+        fs::write(&file, actual).unwrap();
+    } else if actual != expected {
+        panic!("{} has changed. If that's on purpose, rerun with UPDATE_GOLDEN=1.\n\n\
+                expected:\n{}\n\
+                got:\n{}",
+               file.display(),
+               expected,
+               actual);
+    }
+}
+
+#[test]
+fn movement() {
+    check("movement");
+}
+
+#[test]
+fn items() {
+    check("items");
+}
+
+#[test]
+fn talking() {
+    check("talking");
+}
+
+#[test]
+fn map_and_inventory() {
+    check("map_and_inventory");
+}